@@ -0,0 +1,13 @@
+#!/usr/bin/env python3
+
+from PIL import Image
+import numpy
+import sys
+
+# NPY files hold frames with OpenCV's BGR channel order
+npy_frame = numpy.load(sys.argv[1])
+image_frame = numpy.asarray(Image.open(sys.argv[2]))
+if image_frame.ndim == 3:
+    image_frame = image_frame[:, :, ::-1]
+
+sys.exit(0 if numpy.array_equal(npy_frame, image_frame) else 1)
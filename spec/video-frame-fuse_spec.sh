@@ -54,8 +54,9 @@ Describe "video-frame-fuse"
         mount_and_wait_until_ready() {
             local mount_directory="${1:-"${mount_directory}"}"
             local video_file="${2:-"${SAMPLE_FILE}"}"
+            local extra_arguments=("${@:3}")
 
-            RUST_LOG=info tool --logfile "${temp_directory}/mount.log" "${video_file}" "${mount_directory}"
+            RUST_LOG=info tool --logfile "${temp_directory}/mount.log" "${extra_arguments[@]}" "${video_file}" "${mount_directory}"
 
             timeout_at=$(( "$(date +%s)" + 5 ))
             while [[ ! $(ls -A "${mount_directory}" 2> /dev/null) ]]; do
@@ -72,6 +73,13 @@ Describe "video-frame-fuse"
             done
         }
 
+        create_video_directory() {
+            video_directory="${temp_directory}/videos"
+            mkdir -p "${video_directory}"
+            cp "${SAMPLE_FILE}" "${video_directory}/first.mp4"
+            cp "${SAMPLE_FILE}" "${video_directory}/second.mp4"
+        }
+
         extract_frame() {
             local frame_number="$1"
             local output_file="$2"
@@ -91,6 +99,45 @@ Describe "video-frame-fuse"
             fi
         }
 
+        extract_all_frames() {
+            local output_directory="$1"
+            local video_file="${2:-"${SAMPLE_FILE}"}"
+
+            mkdir -p "${output_directory}"
+            local logs_location="${temp_directory}/ffmpeg.${RANDOM}.out"
+            # Passing through frames, so that ffmpeg does not duplicate or drop frames to match a frame rate
+            ffmpeg -i "${video_file}" -fps_mode passthrough -start_number 0 "${output_directory}/frame-%d.png" \
+                2> "${logs_location}"
+
+            if [[ ! -f "${output_directory}/frame-0.png" ]]; then
+                >&2 echo "Output files not produced - ffmpeg logs: $(cat "${logs_location}")"
+                exit 1
+            fi
+        }
+
+        compare_all_frames() {
+            local extracted_frames_directory="$1"
+
+            local number_of_extracted_frames
+            number_of_extracted_frames="$(find "${extracted_frames_directory}" -name '*.png' | wc -l)"
+            local number_of_mounted_frames
+            number_of_mounted_frames="$(ls "${mount_directory}/by-frame" | wc -l)"
+            if [[ "${number_of_extracted_frames}" -ne "${number_of_mounted_frames}" ]]; then
+                >&2 echo "Number of frames differ: ${number_of_extracted_frames} (ffmpeg) != ${number_of_mounted_frames} (mount)"
+                return 1
+            fi
+
+            local frame_number similarity
+            for ((frame_number = 0; frame_number < number_of_extracted_frames; frame_number++)); do
+                similarity="$(calculate_image_similarity "$(get_mount_frame_location "${frame_number}")" \
+                    "${extracted_frames_directory}/frame-${frame_number}.png")"
+                if ! python -c "import sys; sys.exit(0 if ${similarity} < 0.01 else 1)"; then
+                    >&2 echo "Frame ${frame_number} differs from ffmpeg extraction (dssim=${similarity})"
+                    return 1
+                fi
+            done
+        }
+
         extract_greyscale_frame() {
             local frame_number="$1"
             local output_file="$2"
@@ -123,6 +170,20 @@ Describe "video-frame-fuse"
             echo "${mount_directory}/by-frame/frame-${frame_number}/${frame_type}/frame-${frame_number}.${image_type}"
         }
 
+        write_config_in_chunks() {
+            local frame_number="$1"
+            local frame_type="$2"
+            shift 2
+
+            local config_location="${mount_directory}/by-frame/frame-${frame_number}/${frame_type}/config.yml"
+            # Each chunk is written separately to the same open file
+            {
+                for chunk in "$@"; do
+                    printf '%s' "${chunk}"
+                done
+            } > "${config_location}"
+        }
+
         change_config() {
             local frame_number="$1"
             local frame_type="$2"
@@ -153,6 +214,55 @@ Describe "video-frame-fuse"
                 | jq length
         }
 
+        get_image_size() {
+            python3 -c 'import sys; from PIL import Image; print("%dx%d" % Image.open(sys.argv[1]).size)' "$1"
+        }
+
+        get_file_size() {
+            # Note: reading the file, rather than using its attributes, which may be cached
+            cat "$1" | wc -c
+        }
+
+        read_whilst_changing_config() {
+            local frame_number="$1"
+            local frame_type="$2"
+            local output_location="$3"
+
+            exec 3< "$(get_mount_frame_location "${frame_number}" "${frame_type}")"
+            dd bs=1024 count=1 <&3 > "${output_location}" 2> /dev/null
+            change_config "${frame_number}" "${frame_type}" width 64
+            cat <&3 >> "${output_location}"
+            exec 3<&-
+        }
+
+        read_frames_concurrently() {
+            local output_directory="$1"
+            local frame_number
+
+            mkdir -p "${output_directory}"
+            for frame_number in {0..9}; do
+                cat "$(get_mount_frame_location "${frame_number}" edges)" > "${output_directory}/frame-${frame_number}.png" &
+            done
+            wait
+            for frame_number in {0..9}; do
+                cmp "${output_directory}/frame-${frame_number}.png" "$(get_mount_frame_location "${frame_number}" edges)" \
+                    || return 1
+            done
+        }
+
+        get_raw_shape_size() {
+            local raw_frame_location="$1"
+            awk '/^(height|width|channels):/ {size = (size ? size : 1) * $2} END {print size}' \
+                "${raw_frame_location}.shape.yml"
+        }
+
+        compare_raw_frame_size() {
+            local raw_frame_location="$1"
+            # Note: the size of a frame is only known once it has been read
+            cat "${raw_frame_location}" > /dev/null
+            [[ "$(stat -c %s "${raw_frame_location}")" -eq "$(get_raw_shape_size "${raw_frame_location}")" ]]
+        }
+
         math_value() {
             local test_operator="$1"
             local operand_2="$2"
@@ -209,11 +319,196 @@ Describe "video-frame-fuse"
             When call mount_and_wait_until_ready
             The status should equal 0
             The path "${mount_directory}/by-frame" should be directory
+            The path "${mount_directory}/by-frame/frame-0" should be directory
             The path "${mount_directory}/by-frame/frame-1" should be directory
             The path "${mount_directory}/by-frame/frame-1/original" should be directory
             The path "${mount_directory}/by-frame/frame-1/original/frame-1.jpg" should be file
         End
 
+        It "has video metadata files"
+            When call mount_and_wait_until_ready
+            The status should equal 0
+            The path "${mount_directory}/video.yml" should be file
+            The path "${mount_directory}/video.json" should be file
+        End
+
+        It "has video metadata with number of frames"
+            BeforeCall mount_and_wait_until_ready
+            When call jq .number_of_frames "${mount_directory}/video.json"
+            The status should equal 0
+            The output should satisfy math_value -gt 0
+        End
+
+        It "does not list cache statistics"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "cat '${mount_directory}/cache-statistics.yml' > /dev/null"
+            When call ls "${mount_directory}"
+            The status should equal 0
+            The output should not include "cache-statistics.yml"
+        End
+
+        It "has cache statistics"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "cat '${mount_directory}/by-frame/frame-1/original/frame-1.png' > /dev/null"
+            When call cat "${mount_directory}/cache-statistics.yml"
+            The status should equal 0
+            The output should include "decoded:"
+            The output should include "encoded:"
+            The output should include "hits:"
+            The output should include "misses:"
+        End
+
+        It "lists images only once accessed by default"
+            BeforeCall mount_and_wait_until_ready
+            When call ls "${mount_directory}/by-frame/frame-30/original"
+            The status should equal 0
+            The output should not include "frame-30.png"
+        End
+
+        It "lists every image when listing all"
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --listing all"
+            When call ls "${mount_directory}/by-frame/frame-30/original"
+            The status should equal 0
+            The output should include "frame-30.png"
+            The output should include "frame-30.jpg"
+            The output should include "frame-30.rgb.shape.yml"
+        End
+
+        It "lists images of only the chosen formats"
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --listing formats=png,jpg"
+            When call ls "${mount_directory}/by-frame/frame-30/original"
+            The status should equal 0
+            The output should include "frame-30.png"
+            The output should include "frame-30.jpg"
+            The output should not include "frame-30.webp"
+        End
+
+        It "has a manifest of only the chosen formats"
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --listing formats=png"
+            When call cat "${mount_directory}/by-frame/frame-30/original/manifest.csv"
+            The status should equal 0
+            The output should include "frame-30.png"
+            The output should not include "frame-30.jpg"
+        End
+
+        It "rejects invalid listing modes"
+            When run tool --foreground --listing formats=gif "${SAMPLE_FILE}" "${mount_directory}"
+            The status should not equal 0
+            The stderr should include "Invalid image type: gif"
+        End
+
+        It "lists frames without encoding their images"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "ls -l '${mount_directory}/by-frame/frame-30/original' > /dev/null"
+            When call yq eval '.encoded.misses' "${mount_directory}/cache-statistics.yml"
+            The status should equal 0
+            The output should equal 0
+        End
+
+        It "reports the size of images once they have been read"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "get_file_size '$(get_mount_frame_location 30 original png)' > '${temp_directory}/read-size'"
+            When call stat -c %s "$(get_mount_frame_location 30 original png)"
+            The status should equal 0
+            The output should equal "$(cat "${temp_directory}/read-size")"
+        End
+
+        It "reports the size of computed data once it has been read"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "get_file_size '${mount_directory}/by-frame/frame-30/black-and-white/computed.yml' > '${temp_directory}/read-size'"
+            When call stat -c %s "${mount_directory}/by-frame/frame-30/black-and-white/computed.yml"
+            The status should equal 0
+            The output should equal "$(cat "${temp_directory}/read-size")"
+        End
+
+        It "reads images opened before their configuration changed as they were"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "cp '$(get_mount_frame_location 31 resized)' '${temp_directory}/before.png'"
+            BeforeCall "read_whilst_changing_config 31 resized '${temp_directory}/during.png'"
+            When call cmp "${temp_directory}/before.png" "${temp_directory}/during.png"
+            The status should equal 0
+        End
+
+        It "has by-time directory structure"
+            When call mount_and_wait_until_ready
+            The status should equal 0
+            The path "${mount_directory}/by-time" should be directory
+            The path "${mount_directory}/by-time/00-00-00.000/original/frame-0.jpg" should be file
+            The path "${mount_directory}/by-time/00-00-01.234/greyscale" should be directory
+            The path "${mount_directory}/by-time/99-00-00.000" should not be exist
+        End
+
+        It "lists frame timestamps in by-time directory"
+            BeforeCall mount_and_wait_until_ready
+            When call ls "${mount_directory}/by-time"
+            The status should equal 0
+            The line 1 of output should equal "00-00-00.000"
+        End
+
+        It "can mount a directory of videos"
+            BeforeCall create_video_directory
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${temp_directory}/videos'"
+            When call ls "${mount_directory}"
+            The status should equal 0
+            The line 1 of output should equal "first.mp4"
+            The line 2 of output should equal "second.mp4"
+            The path "${mount_directory}/first.mp4/by-frame/frame-1/original/frame-1.jpg" should be file
+        End
+
+        It "shows videos added to a mounted directory"
+            BeforeCall create_video_directory
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${temp_directory}/videos'"
+            BeforeCall "cp '${SAMPLE_FILE}' '${temp_directory}/videos/third.mp4'"
+            When call ls "${mount_directory}"
+            The status should equal 0
+            The output should include "third.mp4"
+        End
+
+        It "hides videos removed from a mounted directory"
+            BeforeCall create_video_directory
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${temp_directory}/videos'"
+            BeforeCall "rm '${temp_directory}/videos/second.mp4'"
+            When call ls "${mount_directory}"
+            The status should equal 0
+            The output should equal "first.mp4"
+        End
+
+        It "skips unreadable videos in a mounted directory"
+            BeforeCall create_video_directory
+            BeforeCall "echo 'not a video' > '${temp_directory}/videos/corrupt.mp4'"
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${temp_directory}/videos'"
+            When call ls "${mount_directory}"
+            The status should equal 0
+            The output should include "first.mp4"
+            The output should not include "corrupt.mp4"
+        End
+
+        It "shows videos that become readable in a mounted directory"
+            BeforeCall create_video_directory
+            BeforeCall "touch '${temp_directory}/videos/third.mp4'"
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${temp_directory}/videos'"
+            BeforeCall "ls '${mount_directory}' > /dev/null"
+            BeforeCall "cp '${SAMPLE_FILE}' '${temp_directory}/videos/third.mp4'"
+            When call ls "${mount_directory}"
+            The status should equal 0
+            The output should include "third.mp4"
+        End
+
+        It "lists frames in by-frame directory"
+            BeforeCall mount_and_wait_until_ready
+            When call ls "${mount_directory}/by-frame"
+            The status should equal 0
+            The output should include "frame-1"
+            The output should include "frame-42"
+        End
+
+        It "lists frames in by-frame directory with distinct inode numbers"
+            BeforeCall mount_and_wait_until_ready
+            When call bash -c "ls -A -i '${mount_directory}/by-frame' | awk '{print \$1}' | sort | uniq -d"
+            The status should equal 0
+            The output should equal ""
+        End
+
         It "can walk FUSE FS"
             BeforeCall mount_and_wait_until_ready
             When call find "${mount_directory}"
@@ -221,11 +516,177 @@ Describe "video-frame-fuse"
             The output should not equal ""
         End
 
+        It "rejects crops outside of the frame"
+            BeforeCall mount_and_wait_until_ready
+            When call change_config 21 cropped x 100000
+            The status should not equal 0
+            The stderr should not equal ""
+        End
+
+        It "rejects invalid configurations as invalid arguments"
+            BeforeCall mount_and_wait_until_ready
+            When call change_config 24 resized width 0
+            The status should not equal 0
+            The stderr should include "Invalid argument"
+        End
+
+        It "explains why configurations are rejected in config.error"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "change_config 24 resized width 0 2> /dev/null"
+            When call cat "${mount_directory}/by-frame/frame-24/resized/config.error"
+            The status should equal 0
+            The output should include "width must be between 1"
+        End
+
+        It "rejects configurations with unknown fields"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "change_config 24 black-and-white treshold 100 2> /dev/null"
+            When call cat "${mount_directory}/by-frame/frame-24/black-and-white/config.error"
+            The status should equal 0
+            The output should include "Unknown fields: treshold"
+        End
+
+        It "applies configurations renamed over config.yml"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "printf 'width: 64\\nheight: 64\\nfit: cover\\n' > '${mount_directory}/by-frame/frame-25/resized/.config.yml.swp'"
+            BeforeCall "mv '${mount_directory}/by-frame/frame-25/resized/.config.yml.swp' '${mount_directory}/by-frame/frame-25/resized/config.yml'"
+            When call get_image_size "$(get_mount_frame_location 25 resized)"
+            The status should equal 0
+            The output should equal "64x64"
+        End
+
+        It "rejects invalid configurations renamed over config.yml"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "echo 'width: 0' > '${mount_directory}/by-frame/frame-25/resized/config.yml~'"
+            When call mv "${mount_directory}/by-frame/frame-25/resized/config.yml~" "${mount_directory}/by-frame/frame-25/resized/config.yml"
+            The status should not equal 0
+            The stderr should include "Invalid argument"
+        End
+
+        It "can remove temporary files in view directories"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "touch '${mount_directory}/by-frame/frame-25/resized/config.yml~'"
+            BeforeCall "rm '${mount_directory}/by-frame/frame-25/resized/config.yml~'"
+            When call ls -a "${mount_directory}/by-frame/frame-25/resized"
+            The status should equal 0
+            The output should include "config.yml"
+            The output should not include "config.yml~"
+        End
+
+        It "rejects temporary files outside of view directories"
+            BeforeCall mount_and_wait_until_ready
+            When call touch "${mount_directory}/by-frame/frame-25/config.yml~"
+            The status should not equal 0
+            The stderr should include "Permission denied"
+        End
+
+        It "applies video-wide configurations to frames"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "printf 'width: 64\\nheight: 64\\nfit: cover\\n' > '${mount_directory}/by-frame/.config/resized.yml'"
+            When call get_image_size "$(get_mount_frame_location 26 resized)"
+            The status should equal 0
+            The output should equal "64x64"
+        End
+
+        It "overrides video-wide configurations with frame configurations"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "printf 'width: 64\\nheight: 64\\nfit: cover\\n' > '${mount_directory}/by-frame/.config/resized.yml'"
+            BeforeCall "change_config 27 resized width 32"
+            When call get_image_size "$(get_mount_frame_location 27 resized)"
+            The status should equal 0
+            The output should equal "32x64"
+        End
+
+        It "inherits video-wide configurations again once frame configurations are cleared"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "change_config 27 resized width 32"
+            BeforeCall ": > '${mount_directory}/by-frame/frame-27/resized/config.yml'"
+            BeforeCall "printf 'width: 64\\nheight: 64\\nfit: cover\\n' > '${mount_directory}/by-frame/.config/resized.yml'"
+            When call get_image_size "$(get_mount_frame_location 27 resized)"
+            The status should equal 0
+            The output should equal "64x64"
+        End
+
+        It "inherits video-wide configurations again once frame configurations are removed"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "change_config 27 resized width 32"
+            BeforeCall "rm '${mount_directory}/by-frame/frame-27/resized/config.yml'"
+            BeforeCall "printf 'width: 64\\nheight: 64\\nfit: cover\\n' > '${mount_directory}/by-frame/.config/resized.yml'"
+            When call head -n 1 "${mount_directory}/by-frame/frame-27/resized/config.yml"
+            The status should equal 0
+            The output should include "Inherited from the video-wide configuration"
+        End
+
+        It "shows where the configuration of a frame came from"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "printf 'width: 64\\n' > '${mount_directory}/by-frame/.config/resized.yml'"
+            When call head -n 1 "${mount_directory}/by-frame/frame-28/resized/config.yml"
+            The status should equal 0
+            The output should include "Inherited from the video-wide configuration"
+        End
+
+        It "rejects invalid video-wide configurations"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "echo 'width: 0' > '${temp_directory}/resized.yml'"
+            When call cp "${temp_directory}/resized.yml" "${mount_directory}/by-frame/.config/resized.yml"
+            The status should not equal 0
+            The stderr should include "Invalid argument"
+        End
+
+        It "saves changed configurations to the state file"
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --state-file '${temp_directory}/state.yml'"
+            BeforeCall "change_config 29 resized width 64"
+            When call yq eval '.videos[].frames[].resized.width' "${temp_directory}/state.yml"
+            The status should equal 0
+            The output should equal 64
+        End
+
+        It "does not save configurations that are the same as those inherited"
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --state-file '${temp_directory}/state.yml'"
+            BeforeCall "cp '${mount_directory}/by-frame/frame-29/resized/config.yml' '${temp_directory}/config.yml'"
+            BeforeCall "change_config 29 resized width 64"
+            BeforeCall "cp '${temp_directory}/config.yml' '${mount_directory}/by-frame/frame-29/resized/config.yml'"
+            When call yq eval '[.videos[].frames[]] | length' "${temp_directory}/state.yml"
+            The status should equal 0
+            The output should equal 0
+        End
+
+        It "restores saved configurations when remounted"
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --state-file '${temp_directory}/state.json'"
+            BeforeCall "change_config 29 resized width 64"
+            BeforeCall "change_config 29 resized height 64"
+            BeforeCall "change_config 29 resized fit '\"cover\"'"
+            BeforeCall cleanup
+            BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --state-file '${temp_directory}/state.json'"
+            When call get_image_size "$(get_mount_frame_location 29 resized)"
+            The status should equal 0
+            The output should equal "64x64"
+        End
+
+        It "applies truncation of configurations that are not open"
+            BeforeCall mount_and_wait_until_ready
+            BeforeCall "change_config 29 resized width 64"
+            BeforeCall "truncate -s 0 '${mount_directory}/by-frame/frame-29/resized/config.yml'"
+            When call get_image_size "$(get_mount_frame_location 29 resized)"
+            The status should equal 0
+            The output should equal "$(get_image_size "$(get_mount_frame_location 29 original)")"
+        End
+
+        It "rejects writes to read-only files"
+            BeforeCall mount_and_wait_until_ready
+            When run bash -c "echo 'image_type,location' > '${mount_directory}/by-frame/frame-24/resized/manifest.csv'"
+            The status should not equal 0
+            The stderr should include "Permission denied"
+        End
+
         Describe "can initialise"
             Parameters
                 original
                 greyscale
                 black-and-white
+                cropped
+                resized
+                edges
             End
 
             It "$1 frame images"
@@ -259,6 +720,27 @@ Describe "video-frame-fuse"
                 The output should satisfy math_value -lt 0.01
             End
 
+            It "frames read concurrently"
+                BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --workers 4"
+                When call read_frames_concurrently "${temp_directory}/frames"
+                The status should equal 0
+            End
+
+            It "every frame, numbered from 0"
+                BeforeCall "extract_all_frames '${temp_directory}/frames'"
+                BeforeCall mount_and_wait_until_ready
+                When call compare_all_frames "${temp_directory}/frames"
+                The status should equal 0
+            End
+
+            It "first frame as frame 1 when numbering from 1"
+                BeforeCall "extract_frame 0 '${temp_directory}/frame-0.png'"
+                BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --first-frame-number 1"
+                When call calculate_image_similarity "$(get_mount_frame_location 1 original)" "${temp_directory}/frame-0.png"
+                The status should equal 0
+                The output should satisfy math_value -lt 0.01
+            End
+
             It "greyscale frame contents"
                 BeforeCall "extract_greyscale_frame 36 '${temp_directory}/frame-36.png'"
                 BeforeCall mount_and_wait_until_ready
@@ -276,6 +758,24 @@ Describe "video-frame-fuse"
                 The output should satisfy math_value -lt 0.1
             End
 
+            It "user-declared pipeline view frame contents"
+                BeforeCall "extract_black_and_white_frame 13 '${temp_directory}/frame-13.png' 50"
+                BeforeCall "printf 'thresholded:\\n  steps:\\n  - greyscale\\n  - threshold: {threshold: 128}\\n' > '${temp_directory}/views.yml'"
+                BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --views '${temp_directory}/views.yml'"
+                When call calculate_image_similarity "$(get_mount_frame_location 13 thresholded)" "${temp_directory}/frame-13.png" 2
+                The status should equal 0
+                The output should satisfy math_value -lt 0.1
+            End
+
+            It "user-declared pipeline view with changed configuration"
+                BeforeCall "printf 'blurred:\\n  steps:\\n  - gaussian-blur: {kernel_size: 9}\\n' > '${temp_directory}/views.yml'"
+                BeforeCall "mount_and_wait_until_ready '${mount_directory}' '${SAMPLE_FILE}' --views '${temp_directory}/views.yml'"
+                BeforeCall "change_config 13 blurred steps '[\"greyscale\"]'"
+                When call calculate_image_similarity "$(get_mount_frame_location 13 blurred)" "$(get_mount_frame_location 13 greyscale)"
+                The status should equal 0
+                The output should satisfy math_value -lt 0.01
+            End
+
             It "greyscale frame with number of colours in correct range"
                 BeforeCall mount_and_wait_until_ready
                 When call get_number_of_colours "$(get_mount_frame_location 13 greyscale)"
@@ -284,12 +784,117 @@ Describe "video-frame-fuse"
                 The output should satisfy math_value -gt 2
             End
 
+            It "original frame as a NumPy array"
+                BeforeCall mount_and_wait_until_ready
+                When call "${repository_root_directory}/tests/acceptance/scripts/image/compare-npy-image.py" \
+                    "$(get_mount_frame_location 42 original npy)" "$(get_mount_frame_location 42 original png)"
+                The status should equal 0
+            End
+
+            It "greyscale frame as a NumPy array"
+                BeforeCall mount_and_wait_until_ready
+                When call "${repository_root_directory}/tests/acceptance/scripts/image/compare-npy-image.py" \
+                    "$(get_mount_frame_location 36 greyscale npy)" "$(get_mount_frame_location 36 greyscale png)"
+                The status should equal 0
+            End
+
+            Describe "raw"
+                Parameters
+                    original rgb
+                    original bgr
+                    original gray
+                    greyscale rgb
+                    black-and-white gray
+                End
+
+                It "$1 frame as a $2 buffer with the size described by its shape"
+                    BeforeCall mount_and_wait_until_ready
+                    When call compare_raw_frame_size "$(get_mount_frame_location 7 "$1" "$2")"
+                    The status should equal 0
+                End
+            End
+
+            It "jpg frames encoded with the configured quality"
+                BeforeCall mount_and_wait_until_ready
+                BeforeCall "get_file_size '$(get_mount_frame_location 11 original jpg)' > '${temp_directory}/default-size'"
+                BeforeCall "change_config 11 original encoding.jpg.quality 5"
+                When call get_file_size "$(get_mount_frame_location 11 original jpg)"
+                The status should equal 0
+                The output should satisfy math_value -lt "$(cat "${temp_directory}/default-size")"
+            End
+
+            It "cropped frame of the configured size"
+                BeforeCall mount_and_wait_until_ready
+                BeforeCall "change_config 21 cropped width 100"
+                BeforeCall "change_config 21 cropped height 0.5"
+                When call get_image_size "$(get_mount_frame_location 21 cropped)"
+                The status should equal 0
+                The output should equal "100x$(( $(jq .height "${mount_directory}/video.json") / 2 ))"
+            End
+
+            It "resized frame of the configured size"
+                BeforeCall mount_and_wait_until_ready
+                BeforeCall "change_config 22 resized width 64"
+                BeforeCall "change_config 22 resized height 64"
+                BeforeCall "change_config 22 resized fit '\"cover\"'"
+                When call get_image_size "$(get_mount_frame_location 22 resized)"
+                The status should equal 0
+                The output should equal "64x64"
+            End
+
+            It "resized frame configured by a config written in several chunks"
+                BeforeCall mount_and_wait_until_ready
+                BeforeCall "write_config_in_chunks 23 resized $'width: 64\\nheight' $': 64\\nfit: cover\\n'"
+                When call get_image_size "$(get_mount_frame_location 23 resized)"
+                The status should equal 0
+                The output should equal "64x64"
+            End
+
             It "black-and-white frame with only 2 colours"
                 BeforeCall mount_and_wait_until_ready
                 When call get_number_of_colours "$(get_mount_frame_location 13 black-and-white)"
                 The status should equal 0
                 The output should equal 2
             End
+
+            It "adaptive black-and-white frame with only 2 colours"
+                BeforeCall mount_and_wait_until_ready
+                BeforeCall "change_config 14 black-and-white method '\"adaptive-gaussian\"'"
+                BeforeCall "change_config 14 black-and-white block_size 21"
+                When call get_number_of_colours "$(get_mount_frame_location 14 black-and-white)"
+                The status should equal 0
+                The output should equal 2
+            End
+
+            It "canny edges frame with only 2 colours"
+                BeforeCall mount_and_wait_until_ready
+                BeforeCall "change_config 16 edges blur 5"
+                When call get_number_of_colours "$(get_mount_frame_location 16 edges)"
+                The status should equal 0
+                The output should equal 2
+            End
+
+            It "sobel edges frame the same size as the frame"
+                BeforeCall mount_and_wait_until_ready
+                BeforeCall "change_config 17 edges operator '\"sobel\"'"
+                When call get_image_size "$(get_mount_frame_location 17 edges)"
+                The status should equal 0
+                The output should equal "$(get_image_size "$(get_mount_frame_location 17 original)")"
+            End
+
+            It "black-and-white computed threshold"
+                BeforeCall mount_and_wait_until_ready
+                When call yq eval '.method + " " + (.threshold | tag)' "${mount_directory}/by-frame/frame-13/black-and-white/computed.yml"
+                The status should equal 0
+                The output should equal "otsu !!float"
+            End
+
+            It "rejects the global black-and-white method without a threshold"
+                BeforeCall mount_and_wait_until_ready
+                When call change_config 15 black-and-white method '"global"'
+                The status should not equal 0
+                The stderr should not equal ""
+            End
         End
     End
 End
@@ -1,17 +1,33 @@
+use crate::fuse_fs::ListingMode;
 use clap::{App, Arg};
 use indoc::indoc;
+use std::thread::available_parallelism;
 
 const LOG_LOCATION: &str = "logfile";
 const FOREGROUND_PARAMETER: &str = "foreground";
 const VIDEO_LOCATION_PARAMETER: &str = "video-location";
 const FUSE_MOUNT_LOCATION_PARAMETER: &str = "fuse-mount-location";
+const FIRST_FRAME_NUMBER_PARAMETER: &str = "first-frame-number";
+const DECODED_CACHE_SIZE_PARAMETER: &str = "decoded-cache-size";
+const ENCODED_CACHE_SIZE_PARAMETER: &str = "encoded-cache-size";
+const VIEWS_PARAMETER: &str = "views";
+const STATE_FILE_PARAMETER: &str = "state-file";
+const WORKERS_PARAMETER: &str = "workers";
+const LISTING_PARAMETER: &str = "listing";
 
 #[derive(Debug)]
 pub struct Configuration {
     pub log_location: Option<String>,
     pub foreground: bool,
-    pub video_location: String,
+    pub video_locations: Vec<String>,
     pub fuse_mount_location: String,
+    pub first_frame_number: u64,
+    pub decoded_cache_size: usize,
+    pub encoded_cache_size: usize,
+    pub views_location: Option<String>,
+    pub state_location: Option<String>,
+    pub number_of_workers: usize,
+    pub listing_mode: ListingMode,
 }
 
 pub fn parse_configuration() -> Configuration {
@@ -33,10 +49,69 @@ pub fn parse_configuration() -> Configuration {
                 .required(false)
                 .help("run in foreground (default is to daemonize)"),
         )
+        .arg(
+            Arg::with_name(FIRST_FRAME_NUMBER_PARAMETER)
+                .long(&format!("--{}", FIRST_FRAME_NUMBER_PARAMETER))
+                .required(false)
+                .takes_value(true)
+                .possible_values(&["0", "1"])
+                .default_value("0")
+                .help("number given to the first frame of the video (frames are numbered consecutively from it)"),
+        )
+        .arg(
+            Arg::with_name(DECODED_CACHE_SIZE_PARAMETER)
+                .long(&format!("--{}", DECODED_CACHE_SIZE_PARAMETER))
+                .required(false)
+                .takes_value(true)
+                .default_value("512M")
+                .validator(|value| parse_byte_size(&value).map(|_| ()))
+                .help("maximum size of the cache of decoded frames, in bytes (suffixes K, M and G are supported)"),
+        )
+        .arg(
+            Arg::with_name(ENCODED_CACHE_SIZE_PARAMETER)
+                .long(&format!("--{}", ENCODED_CACHE_SIZE_PARAMETER))
+                .required(false)
+                .takes_value(true)
+                .default_value("64M")
+                .validator(|value| parse_byte_size(&value).map(|_| ()))
+                .help("maximum size of the cache of encoded images, in bytes (suffixes K, M and G are supported)"),
+        )
+        .arg(
+            Arg::with_name(VIEWS_PARAMETER)
+                .long(&format!("--{}", VIEWS_PARAMETER))
+                .required(false)
+                .takes_value(true)
+                .help("location of a YAML file declaring additional views, as pipelines of operations applied to each frame"),
+        )
+        .arg(
+            Arg::with_name(STATE_FILE_PARAMETER)
+                .long(&format!("--{}", STATE_FILE_PARAMETER))
+                .required(false)
+                .takes_value(true)
+                .help("location of a YAML (or JSON, if it has a .json extension) file that changed configurations are saved to and loaded from, so that they persist across mounts"),
+        )
+        .arg(
+            Arg::with_name(WORKERS_PARAMETER)
+                .long(&format!("--{}", WORKERS_PARAMETER))
+                .required(false)
+                .takes_value(true)
+                .validator(|value| parse_number_of_workers(&value).map(|_| ()))
+                .help("number of threads that produce the data of files concurrently (default is the number of CPUs)"),
+        )
+        .arg(
+            Arg::with_name(LISTING_PARAMETER)
+                .long(&format!("--{}", LISTING_PARAMETER))
+                .required(false)
+                .takes_value(true)
+                .default_value("lazy")
+                .validator(|value| ListingMode::parse(&value).map(|_| ()))
+                .help("image files listed in views: lazy (only once accessed), all, or formats=<types> (e.g. formats=png,jpg)"),
+        )
         .arg(
             Arg::with_name(VIDEO_LOCATION_PARAMETER)
-                .help("location of the video file to use")
-                .required(true),
+                .help("location of the video file(s), or directories of video files, to use")
+                .required(true)
+                .multiple(true),
         )
         .arg(
             Arg::with_name(FUSE_MOUNT_LOCATION_PARAMETER)
@@ -52,13 +127,82 @@ pub fn parse_configuration() -> Configuration {
     Configuration {
         log_location: matches.value_of(LOG_LOCATION).map(str::to_string),
         foreground: matches.is_present(FOREGROUND_PARAMETER),
-        video_location: matches
-            .value_of(VIDEO_LOCATION_PARAMETER)
+        video_locations: matches
+            .values_of(VIDEO_LOCATION_PARAMETER)
             .unwrap()
-            .to_string(),
+            .map(str::to_string)
+            .collect(),
         fuse_mount_location: matches
             .value_of(FUSE_MOUNT_LOCATION_PARAMETER)
             .unwrap()
             .to_string(),
+        first_frame_number: matches
+            .value_of(FIRST_FRAME_NUMBER_PARAMETER)
+            .unwrap()
+            .parse()
+            .unwrap(),
+        decoded_cache_size: parse_byte_size(
+            matches.value_of(DECODED_CACHE_SIZE_PARAMETER).unwrap(),
+        )
+        .unwrap(),
+        encoded_cache_size: parse_byte_size(
+            matches.value_of(ENCODED_CACHE_SIZE_PARAMETER).unwrap(),
+        )
+        .unwrap(),
+        views_location: matches.value_of(VIEWS_PARAMETER).map(str::to_string),
+        state_location: matches.value_of(STATE_FILE_PARAMETER).map(str::to_string),
+        number_of_workers: match matches.value_of(WORKERS_PARAMETER) {
+            Some(x) => parse_number_of_workers(x).unwrap(),
+            None => available_parallelism().map_or(1, usize::from),
+        },
+        listing_mode: ListingMode::parse(matches.value_of(LISTING_PARAMETER).unwrap()).unwrap(),
+    }
+}
+
+// Parses a size in bytes, e.g. `1024`, `512K`, `64M` or `2G` (binary multiples)
+fn parse_byte_size(value: &str) -> Result<usize, String> {
+    let (number, multiplier) = match value.chars().last() {
+        Some('K') | Some('k') => (&value[..value.len() - 1], 1024),
+        Some('M') | Some('m') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('G') | Some('g') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    match number.parse::<usize>() {
+        Ok(x) => x
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("Size is too large: {}", value)),
+        Err(_) => Err(format!("Invalid size: {}", value)),
+    }
+}
+
+fn parse_number_of_workers(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(x) if x > 0 => Ok(x),
+        _ => Err(format!("Invalid number of workers: {}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("0", Ok(0); "when zero")]
+    #[test_case("1000", Ok(1000); "when bytes")]
+    #[test_case("2K", Ok(2048); "when kibibytes")]
+    #[test_case("64M", Ok(64 * 1024 * 1024); "when mebibytes")]
+    #[test_case("1g", Ok(1024 * 1024 * 1024); "when lowercase suffix")]
+    #[test_case("M", Err("Invalid size: M".to_string()); "when no number")]
+    #[test_case("-1", Err("Invalid size: -1".to_string()); "when negative")]
+    #[test_case("1T", Err("Invalid size: 1T".to_string()); "when unknown suffix")]
+    fn byte_size_parsing(value: &str, expected: Result<usize, String>) {
+        assert_eq!(parse_byte_size(value), expected);
+    }
+
+    #[test_case("4", Ok(4); "when positive")]
+    #[test_case("0", Err("Invalid number of workers: 0".to_string()); "when zero")]
+    #[test_case("x", Err("Invalid number of workers: x".to_string()); "when not a number")]
+    fn number_of_workers_parsing(value: &str, expected: Result<usize, String>) {
+        assert_eq!(parse_number_of_workers(value), expected);
     }
 }
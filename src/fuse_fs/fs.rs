@@ -1,42 +1,207 @@
-use crate::fuse_fs::models::nodes::{create_directory_attributes, FuseNode, FuseNodeStore};
+use crate::fuse_fs::library::VideoLibrary;
+use crate::fuse_fs::models::buffers::FileBuffers;
+use crate::fuse_fs::models::file::FileInformation;
+use crate::fuse_fs::models::listings::{DirectoryEntry, DirectoryListings};
+use crate::fuse_fs::models::nodes::{
+    create_directory_attributes, FileFuseNode, FuseNode, FuseNodeStore, ROOT_INODE_NUMBER,
+};
+use fuse::consts::FOPEN_DIRECT_IO;
 use fuse::{
-    FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, ReplyWrite,
-    Request,
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, ReplyWrite, Request,
 };
-use libc::{EIO, ENOENT, EPERM};
+use libc::{c_int, EACCES, EBADF, EEXIST, EINVAL, EIO, ENOENT, EXDEV, O_ACCMODE, O_RDONLY};
 use log::{debug, error};
-use std::cmp::{max, min};
 use std::ffi::OsStr;
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use threadpool::ThreadPool;
 
 const TTL: Duration = Duration::from_secs(1);
+// Used for the attributes of files whose size is not yet known, so that the size is reported as soon
+// as it is known
+const UNKNOWN_SIZE_TTL: Duration = Duration::from_secs(0);
 
 lazy_static! {
     static ref ROOT_DIRECTORY_ATTRIBUTES: FileAttr = create_directory_attributes(1);
 }
 
+// Note: requests are received on a single thread, which owns the nodes. Producing the data of files
+//       (e.g. decoding and encoding frames) is done by a pool of workers, so that requests are not
+//       blocked by files that are slow to produce.
 pub struct VideoFileSystem<'a> {
     pub nodes: FuseNodeStore<'a>,
+    pub library: Option<VideoLibrary>,
+    // When the library was last refreshed (if ever)
+    library_refreshed_at: Option<Instant>,
+    file_buffers: Arc<Mutex<FileBuffers>>,
+    directory_listings: DirectoryListings,
+    number_of_workers: usize,
+    // Note: the workers are only started once the file system is initialised, as threads do not
+    //       survive the process being forked when daemonized
+    workers: Option<ThreadPool>,
+}
+
+impl<'a> VideoFileSystem<'a> {
+    pub fn new(
+        nodes: FuseNodeStore<'a>,
+        library: Option<VideoLibrary>,
+        number_of_workers: usize,
+    ) -> Self {
+        VideoFileSystem {
+            nodes,
+            library,
+            library_refreshed_at: None,
+            file_buffers: Arc::new(Mutex::new(FileBuffers::new())),
+            directory_listings: DirectoryListings::new(),
+            number_of_workers,
+            workers: None,
+        }
+    }
+
+    fn refresh_library(&mut self) {
+        if let Some(library) = self.library.as_mut() {
+            library.refresh(&mut self.nodes);
+            self.library_refreshed_at = Some(Instant::now());
+        }
+    }
+
+    // Refreshes the library unless it has been refreshed within the time that attributes are
+    // cached for. Note: refreshing scans the library's directories, so is not done for every lookup.
+    fn refresh_library_if_stale(&mut self) {
+        match self.library_refreshed_at {
+            Some(x) if x.elapsed() < TTL => {}
+            _ => self.refresh_library(),
+        }
+    }
+
+    // Applies the data written via the given file handle to its file (if it has changed)
+    fn apply_file_buffer(&mut self, file_handle: u64) -> Result<(), String> {
+        let mut file_buffers = self.file_buffers.lock().unwrap();
+        let buffer = match file_buffers.get_mut(file_handle) {
+            Some(x) if x.modified => x,
+            _ => return Ok(()),
+        };
+        // Note: the data is not reapplied if it is invalid, as it will not have changed
+        buffer.modified = false;
+        let node = match self.nodes.get_file_node_mut(buffer.inode_number) {
+            Some(x) => x,
+            None => {
+                // Note: the file can be removed whilst open (e.g. an editor's swap file)
+                debug!(
+                    "Discarding data written to removed file: {}",
+                    buffer.inode_number
+                );
+                return Ok(());
+            }
+        };
+        debug!(
+            "Applying data of length {} written to {}",
+            buffer.data.len(),
+            node.information.name
+        );
+        node.information.set_data(buffer.data.clone())
+    }
+
+    // Gets the inode number of the file with the given name in the given directory (if any)
+    fn get_file_inode_number(&mut self, name: &str, directory_inode_number: u64) -> Option<u64> {
+        match self.nodes.find_node(name, directory_inode_number)? {
+            FuseNode::File(x) => Some(x.get_inode_number()),
+            FuseNode::Directory(_) => None,
+        }
+    }
+
+    // Gets the entries listed in the given directory (if it exists)
+    fn get_directory_entries(
+        &mut self,
+        directory_inode_number: u64,
+    ) -> Option<Vec<DirectoryEntry>> {
+        self.nodes.get_directory_node(directory_inode_number)?;
+
+        let mut entries = vec![
+            DirectoryEntry::new(directory_inode_number, FileType::Directory, "."),
+            DirectoryEntry::new(directory_inode_number, FileType::Directory, ".."),
+        ];
+        // Note: dynamic children are listed from the directory when read (see `readdir`)
+        if self
+            .nodes
+            .get_number_of_dynamic_children(directory_inode_number)
+            .is_none()
+        {
+            entries.extend(
+                self.nodes
+                    .get_nodes_in_directory(directory_inode_number)
+                    .into_iter()
+                    .filter(|fuse_node| match fuse_node {
+                        FuseNode::Directory(_) => true,
+                        FuseNode::File(x) => x.information.listed,
+                    })
+                    .map(|fuse_node| match fuse_node {
+                        FuseNode::Directory(x) => {
+                            DirectoryEntry::new(x.get_inode_number(), FileType::Directory, &x.name)
+                        }
+                        FuseNode::File(x) => DirectoryEntry::new(
+                            x.get_inode_number(),
+                            FileType::RegularFile,
+                            &x.information.name,
+                        ),
+                    }),
+            );
+        }
+        Some(entries)
+    }
+
+    // Scratch files (e.g. those an editor writes before renaming over a configuration) can only
+    // be created in directories with configurable files
+    fn accepts_scratch_files(&mut self, directory_inode_number: u64) -> bool {
+        if self
+            .nodes
+            .get_directory_node(directory_inode_number)
+            .is_none()
+        {
+            return false;
+        }
+        self.nodes
+            .get_nodes_in_directory(directory_inode_number)
+            .into_iter()
+            .any(|fuse_node| match fuse_node {
+                FuseNode::Directory(_) => false,
+                FuseNode::File(x) => x.information.writable && !x.information.scratch,
+            })
+    }
 }
 
 impl Filesystem for VideoFileSystem<'_> {
+    fn init(&mut self, _req: &Request<'_>) -> Result<(), c_int> {
+        self.workers = Some(ThreadPool::with_name(
+            "file-data-worker".to_string(),
+            self.number_of_workers,
+        ));
+        Ok(())
+    }
+
     fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
         let name = name.to_str().expect("Could not convert OsStr to string");
+        if parent == ROOT_INODE_NUMBER {
+            self.refresh_library_if_stale();
+        }
 
         let mut requires_listing = false;
         let inode_number;
         let node = self.nodes.lookup_node(name, parent);
         match node {
             Some(fuse_node) => {
-                let attributes = match fuse_node {
-                    FuseNode::Directory(x) => x.attributes,
+                let (attributes, ttl) = match fuse_node {
+                    FuseNode::Directory(x) => (x.attributes, TTL),
                     FuseNode::File(file_node) => {
-                        requires_listing = !file_node.information.listed;
-                        file_node.get_attributes()
+                        requires_listing =
+                            !file_node.information.listed && !file_node.information.hidden;
+                        get_file_attributes(file_node)
                     }
                 };
                 inode_number = attributes.ino;
-                reply.entry(&TTL, &attributes, 0);
+                reply.entry(&ttl, &attributes, 0);
+                self.nodes.record_lookup(inode_number);
             }
             None => {
                 reply.error(ENOENT);
@@ -58,17 +223,21 @@ impl Filesystem for VideoFileSystem<'_> {
         }
     }
 
+    fn forget(&mut self, _req: &Request<'_>, inode_number: u64, nlookup: u64) {
+        self.nodes.forget(inode_number, nlookup);
+    }
+
     fn getattr(&mut self, _req: &Request, inode_number: u64, reply: ReplyAttr) {
         match self.nodes.get_node(inode_number) {
             Some(fuse_node) => {
-                let attributes = match fuse_node {
-                    FuseNode::Directory(x) => x.attributes,
-                    FuseNode::File(x) => x.get_attributes(),
+                let (attributes, ttl) = match fuse_node {
+                    FuseNode::Directory(x) => (x.attributes, TTL),
+                    FuseNode::File(x) => get_file_attributes(x),
                 };
-                reply.attr(&TTL, &attributes);
+                reply.attr(&ttl, &attributes);
             }
             None => {
-                eprintln!("No node (getattr): {:?}", inode_number);
+                debug!("No node (getattr): {:?}", inode_number);
                 reply.error(ENOENT);
             }
         };
@@ -81,22 +250,281 @@ impl Filesystem for VideoFileSystem<'_> {
         _mode: Option<u32>,
         _uid: Option<u32>,
         _gid: Option<u32>,
-        _size: Option<u64>,
+        size: Option<u64>,
         _atime: Option<SystemTime>,
         _mtime: Option<SystemTime>,
-        _fh: Option<u64>,
+        fh: Option<u64>,
         _crtime: Option<SystemTime>,
         _chgtime: Option<SystemTime>,
         _bkuptime: Option<SystemTime>,
         _flags: Option<u32>,
         reply: ReplyAttr,
     ) {
-        match self.nodes.get_file_node(inode_number) {
+        let node = match self.nodes.get_file_node(inode_number) {
+            Some(x) => x,
             None => {
                 error!("Node not found (setattr): {}", inode_number);
                 reply.error(EIO);
+                return;
+            }
+        };
+        let mut attributes = node.get_attributes();
+
+        if let Some(size) = size {
+            if !node.information.writable {
+                error!("File is read-only: {}", node.information.name);
+                reply.error(EACCES);
+                return;
+            }
+            // Note: truncation via a file handle is applied with the rest of the data written to
+            //       the file, whereas truncation of a file that is not open is applied immediately
+            let mut file_buffers = self.file_buffers.lock().unwrap();
+            match fh
+                .and_then(|x| file_buffers.get_mut(x))
+                .filter(|x| x.writable)
+            {
+                Some(buffer) => buffer.truncate(size as usize),
+                None => {
+                    let node = self.nodes.get_file_node_mut(inode_number).unwrap();
+                    let mut data = node.get_data();
+                    data.resize(size as usize, 0);
+                    if let Err(e) = node.information.set_data(data) {
+                        error!("Could not truncate file {}: {}", node.information.name, e);
+                        reply.error(EINVAL);
+                        return;
+                    }
+                }
+            }
+            attributes.size = size;
+        }
+        reply.attr(&TTL, &attributes);
+    }
+
+    fn open(&mut self, _req: &Request<'_>, inode_number: u64, flags: u32, reply: ReplyOpen) {
+        let node = match self.nodes.get_file_node(inode_number) {
+            Some(x) => x,
+            None => {
+                error!("Node not found (open): {}", inode_number);
+                reply.error(ENOENT);
+                return;
+            }
+        };
+        if flags as i32 & O_ACCMODE == O_RDONLY {
+            // Files of unknown size are read until there is no more data, rather than up to the
+            // size that was reported for them
+            let open_flags = match node.information.get_size() {
+                Some(_) => 0,
+                None => FOPEN_DIRECT_IO,
+            };
+            let data_fetcher = match node.information.get_data_fetcher() {
+                Some(x) => x,
+                None => {
+                    let file_handle = self
+                        .file_buffers
+                        .lock()
+                        .unwrap()
+                        .open_snapshot(inode_number, node.get_data());
+                    reply.opened(file_handle, open_flags);
+                    return;
+                }
+            };
+            // Note: if the worker panics, the reply is dropped, which replies with an error
+            let file_buffers = self.file_buffers.clone();
+            let open_snapshot = move || {
+                let data = data_fetcher();
+                let file_handle = file_buffers
+                    .lock()
+                    .unwrap()
+                    .open_snapshot(inode_number, data);
+                reply.opened(file_handle, open_flags);
+            };
+            match &self.workers {
+                Some(workers) => workers.execute(open_snapshot),
+                None => open_snapshot(),
+            }
+            return;
+        }
+        if !node.information.writable {
+            error!("File is read-only: {}", node.information.name);
+            reply.error(EACCES);
+            return;
+        }
+        let file_handle = self
+            .file_buffers
+            .lock()
+            .unwrap()
+            .open(inode_number, node.information.get_data());
+        reply.opened(file_handle, 0);
+    }
+
+    fn flush(
+        &mut self,
+        _req: &Request<'_>,
+        inode_number: u64,
+        fh: u64,
+        _lock_owner: u64,
+        reply: ReplyEmpty,
+    ) {
+        match self.apply_file_buffer(fh) {
+            Ok(_) => reply.ok(),
+            Err(e) => {
+                error!("Error writing file {}: {}", inode_number, e);
+                reply.error(EINVAL);
+            }
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        inode_number: u64,
+        fh: u64,
+        _flags: u32,
+        _lock_owner: u64,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        // Note: errors cannot be reported to the writer on release (only on flush)
+        if let Err(e) = self.apply_file_buffer(fh) {
+            error!("Error writing file {}: {}", inode_number, e);
+        }
+        self.file_buffers.lock().unwrap().remove(fh);
+        reply.ok();
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _flags: u32,
+        reply: ReplyCreate,
+    ) {
+        let name = name.to_str().expect("Could not convert OsStr to string");
+        if !self.accepts_scratch_files(parent) {
+            error!("Cannot create file {} in directory: {}", name, parent);
+            reply.error(EACCES);
+            return;
+        }
+        if self.nodes.find_node(name, parent).is_some() {
+            reply.error(EEXIST);
+            return;
+        }
+
+        let inode_number = self
+            .nodes
+            .create_and_insert_file(FileInformation::new_scratch(name), parent);
+        let file_handle = self.file_buffers.lock().unwrap().open(inode_number, vec![]);
+        let attributes = self
+            .nodes
+            .get_file_node(inode_number)
+            .expect(&format!(
+                "Could not get created file node: {}",
+                inode_number
+            ))
+            .get_attributes();
+        reply.created(&TTL, &attributes, 0, file_handle, 0);
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let name = name.to_str().expect("Could not convert OsStr to string");
+        let inode_number = match self.get_file_inode_number(name, parent) {
+            Some(x) => x,
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+        let node = self.nodes.get_file_node_mut(inode_number).unwrap();
+        // Note: removing a writable file (e.g. a configuration) clears it, rather than removing it
+        if node.information.writable && !node.information.scratch {
+            match node.information.set_data(vec![]) {
+                Ok(_) => reply.ok(),
+                Err(e) => {
+                    error!("Could not clear file {}: {}", name, e);
+                    reply.error(EINVAL);
+                }
+            }
+            return;
+        }
+        if !node.information.scratch {
+            error!("Cannot remove file: {}", name);
+            reply.error(EACCES);
+            return;
+        }
+        self.nodes.remove_file(inode_number);
+        reply.ok();
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        new_parent: u64,
+        new_name: &OsStr,
+        reply: ReplyEmpty,
+    ) {
+        let name = name.to_str().expect("Could not convert OsStr to string");
+        let new_name = new_name
+            .to_str()
+            .expect("Could not convert OsStr to string");
+        // Note: scratch files only have meaning in the view they were created in
+        if parent != new_parent {
+            reply.error(EXDEV);
+            return;
+        }
+        let inode_number = match self.get_file_inode_number(name, parent) {
+            Some(x) => x,
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+        if !self
+            .nodes
+            .get_file_node(inode_number)
+            .unwrap()
+            .information
+            .scratch
+        {
+            error!("Cannot rename file: {}", name);
+            reply.error(EACCES);
+            return;
+        }
+
+        let target_inode_number = match self.get_file_inode_number(new_name, parent) {
+            Some(x) => x,
+            None => {
+                self.nodes.rename_file(inode_number, new_name);
+                reply.ok();
+                return;
+            }
+        };
+        if target_inode_number == inode_number {
+            reply.ok();
+            return;
+        }
+
+        // Renaming onto an existing file replaces its data in one go (e.g. an atomic
+        // configuration update), so it is only done if the data is valid
+        let data = self.nodes.get_file_node(inode_number).unwrap().get_data();
+        let target_node = self.nodes.get_file_node_mut(target_inode_number).unwrap();
+        if !target_node.information.writable {
+            error!("File is read-only: {}", new_name);
+            reply.error(EACCES);
+            return;
+        }
+        match target_node.information.set_data(data) {
+            Ok(_) => {
+                self.nodes.remove_file(inode_number);
+                reply.ok();
+            }
+            Err(e) => {
+                error!("Error renaming {} to {}: {}", name, new_name, e);
+                reply.error(EINVAL);
             }
-            Some(node) => reply.attr(&TTL, &node.get_attributes()),
         }
     }
 
@@ -104,32 +532,30 @@ impl Filesystem for VideoFileSystem<'_> {
         &mut self,
         _req: &Request,
         ino: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         size: u32,
         reply: ReplyData,
     ) {
-        let node = match self.nodes.get_file_node_mut(ino) {
-            Some(file_node) => file_node,
+        match self.file_buffers.lock().unwrap().get(fh) {
+            Some(buffer) => reply.data(buffer.read(offset as usize, size as usize)),
             None => {
-                error!("Node not found (read): {}", ino);
-                reply.error(ENOENT);
+                error!("File handle not open (read): {}", fh);
+                reply.error(EBADF);
                 return;
             }
-        };
-
-        let data = node.get_data();
-        let end = min(offset as usize + size as usize, data.len());
-        reply.data(&data[offset as usize..end]);
+        }
 
-        node.information.listed = true;
+        if let Some(node) = self.nodes.get_file_node_mut(ino) {
+            node.information.listed = !node.information.hidden;
+        }
     }
 
     fn write(
         &mut self,
         _req: &Request<'_>,
         inode_number: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         data: &[u8],
         _flags: u32,
@@ -141,36 +567,32 @@ impl Filesystem for VideoFileSystem<'_> {
             offset,
             inode_number,
         );
-        let node_result = self.nodes.get_file_node_mut(inode_number);
-        if node_result.is_none() {
-            error!("Node not found (write) {}", inode_number);
-            reply.error(EIO);
-            return;
+        match self
+            .file_buffers
+            .lock()
+            .unwrap()
+            .get_mut(fh)
+            .filter(|x| x.writable)
+        {
+            Some(buffer) => {
+                buffer.write(offset as usize, data);
+                reply.written(data.len() as u32);
+            }
+            None => {
+                error!("File handle not open for writing (write): {}", fh);
+                reply.error(EBADF);
+            }
         }
-        let node = node_result.unwrap();
-
-        let mut data_as_vec;
-        if offset == 0 {
-            data_as_vec = data.to_vec();
-        } else {
-            data_as_vec = node.information.get_data();
-            let end_position: usize = offset as usize + data.len();
-            // XXX: there is likely a more efficient way to support an append operation (common)
-            data_as_vec.resize(max(data_as_vec.len(), end_position), 0);
-            data_as_vec.splice(offset as usize..end_position, data.to_vec());
-        }
-
-        // FIXME: setting the data calls the callback, even though the full data may not be complete
-        let write_result = node.information.set_data(data_as_vec);
-        if write_result.is_err() {
-            error!(
-                "Error writing file \"{}\": {}",
-                node.information.name,
-                write_result.unwrap_err()
-            );
-            reply.error(EPERM);
-        } else {
-            reply.written(data.len() as u32);
+    }
+
+    fn opendir(&mut self, _req: &Request, ino: u64, _flags: u32, reply: ReplyOpen) {
+        if ino == ROOT_INODE_NUMBER {
+            self.refresh_library();
+        }
+
+        match self.get_directory_entries(ino) {
+            Some(entries) => reply.opened(self.directory_listings.open(entries), 0),
+            None => reply.error(ENOENT),
         }
     }
 
@@ -178,49 +600,50 @@ impl Filesystem for VideoFileSystem<'_> {
         &mut self,
         _req: &Request,
         ino: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
-        let node = self.nodes.get_directory_node(ino);
-        if node.is_none() {
-            reply.error(ENOENT);
-            return;
+        let entries = match self.directory_listings.get(fh) {
+            Some(x) => x,
+            None => {
+                reply.error(EBADF);
+                return;
+            }
+        };
+        // Note: the offset given with each entry is that of the next entry
+        let offset = offset as u64;
+        for (i, entry) in entries.iter().enumerate().skip(offset as usize) {
+            if reply.add(entry.inode_number, i as i64 + 1, entry.kind, &entry.name) {
+                reply.ok();
+                return;
+            }
         }
 
-        let mut entries = vec![
-            (ino, FileType::Directory, ".".to_string()),
-            (ino, FileType::Directory, "..".to_string()),
-        ];
-        entries.extend(
-            self.nodes
-                .get_nodes_in_directory(ino)
-                .into_iter()
-                .filter(|fuse_node| match fuse_node {
-                    FuseNode::Directory(_) => true,
-                    FuseNode::File(x) => x.information.listed,
-                })
-                .map(|fuse_node| {
-                    let attributes;
-                    let name;
-                    match fuse_node {
-                        FuseNode::Directory(x) => {
-                            attributes = x.attributes;
-                            name = x.name.to_string();
-                        }
-                        FuseNode::File(x) => {
-                            attributes = x.get_attributes();
-                            name = x.information.name.to_string();
-                        }
-                    };
-                    (attributes.ino as u64, attributes.kind, name)
-                }),
-        );
-
-        for (i, entry) in entries.into_iter().enumerate().skip(offset as usize) {
-            // i + 1 means the index of the next entry
-            reply.add(entry.0, (i + 1) as i64, entry.1, entry.2);
+        // Dynamic children follow the other entries, and are generated from their index so that
+        // listings resume without generating the children before the offset
+        let number_of_entries = entries.len() as u64;
+        let number_of_dynamic_children =
+            self.nodes.get_number_of_dynamic_children(ino).unwrap_or(0);
+        for index in offset.saturating_sub(number_of_entries)..number_of_dynamic_children {
+            let (inode_number, name) = self.nodes.get_dynamic_child(ino, index).unwrap();
+            let next_offset = (number_of_entries + index + 1) as i64;
+            if reply.add(inode_number, next_offset, FileType::Directory, &name) {
+                break;
+            }
         }
         reply.ok();
     }
+
+    fn releasedir(&mut self, _req: &Request, _ino: u64, fh: u64, _flags: u32, reply: ReplyEmpty) {
+        self.directory_listings.remove(fh);
+        reply.ok();
+    }
+}
+
+fn get_file_attributes(file_node: &FileFuseNode) -> (FileAttr, Duration) {
+    match file_node.get_attributes_and_is_size_known() {
+        (attributes, true) => (attributes, TTL),
+        (attributes, false) => (attributes, UNKNOWN_SIZE_TTL),
+    }
 }
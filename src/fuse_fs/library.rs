@@ -0,0 +1,259 @@
+use crate::fuse_fs::models::nodes::{FuseNodeStore, ROOT_INODE_NUMBER};
+use crate::fuse_fs::nodes::{insert_default_video_nodes, VideoNodesOptions};
+use crate::video_processing::{forget_video, get_video_properties};
+use log::{info, warn};
+use std::collections::{HashMap, HashSet};
+use std::fs::{metadata, read_dir};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const VIDEO_FILE_EXTENSIONS: &[&str] = &[
+    "avi", "flv", "m4v", "mkv", "mov", "mp4", "mpeg", "mpg", "mts", "ts", "webm", "wmv",
+];
+
+// Version of a video file, which changes if the file changes (e.g. whilst it is being copied)
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FileVersion {
+    modified: Option<SystemTime>,
+    size: u64,
+}
+
+impl FileVersion {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = metadata(path).ok()?;
+        Some(FileVersion {
+            modified: metadata.modified().ok(),
+            size: metadata.len(),
+        })
+    }
+}
+
+// A video found at the video locations, which is only mounted (with an inode number) if it could be
+// read. Videos are not read again unless their file changes.
+struct LibraryVideo {
+    inode_number: Option<u64>,
+    file_version: Option<FileVersion>,
+}
+
+// Mounts many videos, with a subtree per video (named after the video's file name) at the root
+pub struct VideoLibrary {
+    video_locations: Vec<String>,
+    options: VideoNodesOptions,
+    videos: HashMap<PathBuf, LibraryVideo>,
+}
+
+impl VideoLibrary {
+    pub fn new(video_locations: &[String], options: &VideoNodesOptions) -> Self {
+        VideoLibrary {
+            video_locations: video_locations.to_vec(),
+            options: options.clone(),
+            videos: Default::default(),
+        }
+    }
+
+    // Synchronises the videos in the node store with those currently at the video locations
+    pub fn refresh(&mut self, node_store: &mut FuseNodeStore) {
+        let video_paths = find_videos(&self.video_locations);
+
+        let removed_video_paths: Vec<PathBuf> = self
+            .videos
+            .iter()
+            .filter(|(video_path, video)| {
+                !video_paths.contains(video_path)
+                    || FileVersion::of(video_path) != video.file_version
+            })
+            .map(|(video_path, _)| video_path.clone())
+            .collect();
+        for video_path in removed_video_paths {
+            let video = self.videos.remove(&video_path).unwrap();
+            if let Some(inode_number) = video.inode_number {
+                info!("Removing video: {}", video_path.display());
+                node_store.remove_directory(inode_number, ROOT_INODE_NUMBER);
+                forget_video(&get_video_location(&video_path));
+            }
+        }
+
+        let mut used_names: HashSet<String> = self
+            .videos
+            .iter()
+            .filter(|(_, video)| video.inode_number.is_some())
+            .map(|(video_path, _)| get_video_name(video_path))
+            .collect();
+        for video_path in video_paths {
+            if self.videos.contains_key(&video_path) {
+                continue;
+            }
+            let name = get_video_name(&video_path);
+            if used_names.contains(&name) {
+                warn!(
+                    "Not adding video as another video with the same name has been added: {}",
+                    video_path.display()
+                );
+                continue;
+            }
+
+            let file_version = FileVersion::of(&video_path);
+            let video_location = get_video_location(&video_path);
+            let inode_number = match get_video_properties(&video_location) {
+                Ok(video_properties) if video_properties.number_of_frames > 0 => {
+                    info!("Adding video: {}", video_path.display());
+                    let inode_number =
+                        node_store.create_and_insert_directory(&name, ROOT_INODE_NUMBER);
+                    insert_default_video_nodes(
+                        node_store,
+                        &video_location,
+                        &video_properties,
+                        &self.options,
+                        inode_number,
+                    );
+                    used_names.insert(name);
+                    Some(inode_number)
+                }
+                Ok(_) => {
+                    warn!("Not adding video without frames: {}", video_path.display());
+                    None
+                }
+                Err(e) => {
+                    warn!("Not adding video that could not be read: {}", e);
+                    None
+                }
+            };
+            self.videos.insert(
+                video_path,
+                LibraryVideo {
+                    inode_number,
+                    file_version,
+                },
+            );
+        }
+    }
+}
+
+pub fn find_videos(video_locations: &[String]) -> Vec<PathBuf> {
+    let mut video_paths = vec![];
+    for video_location in video_locations {
+        let path = Path::new(video_location);
+        if !path.is_dir() {
+            if path.exists() {
+                video_paths.push(path.to_path_buf());
+            }
+            continue;
+        }
+
+        let mut directory_video_paths = match read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|entry_path| entry_path.is_file() && is_video_file(entry_path))
+                .collect(),
+            Err(e) => {
+                warn!(
+                    "Could not read video directory \"{}\": {}",
+                    video_location, e
+                );
+                vec![]
+            }
+        };
+        directory_video_paths.sort();
+        video_paths.extend(directory_video_paths);
+    }
+    video_paths
+}
+
+fn is_video_file(path: &Path) -> bool {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some(extension) => VIDEO_FILE_EXTENSIONS.contains(&extension.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+fn get_video_location(video_path: &Path) -> String {
+    video_path
+        .to_str()
+        .expect("Could not convert path to string")
+        .to_string()
+}
+
+fn get_video_name(video_path: &Path) -> String {
+    video_path
+        .file_name()
+        .expect("Expected video path to have a file name")
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, File};
+
+    #[test]
+    fn find_videos_in_directory() {
+        let directory = std::env::temp_dir().join(format!("video-library-{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+        for file_name in ["b.mp4", "a.MKV", "notes.txt"] {
+            File::create(directory.join(file_name)).unwrap();
+        }
+        create_dir_all(directory.join("nested.mp4")).unwrap();
+
+        let video_paths = find_videos(&[directory.to_str().unwrap().to_string()]);
+        remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            video_paths,
+            vec![directory.join("a.MKV"), directory.join("b.mp4")]
+        );
+    }
+
+    #[test]
+    fn refresh_skips_unreadable_videos() {
+        let directory =
+            std::env::temp_dir().join(format!("video-library-unreadable-{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+        File::create(directory.join("empty.mp4")).unwrap();
+
+        let mut node_store = FuseNodeStore::new();
+        let mut library = VideoLibrary::new(
+            &[directory.to_str().unwrap().to_string()],
+            &VideoNodesOptions::default(),
+        );
+        library.refresh(&mut node_store);
+        library.refresh(&mut node_store);
+        remove_dir_all(&directory).unwrap();
+
+        assert!(node_store
+            .lookup_node("empty.mp4", ROOT_INODE_NUMBER)
+            .is_none());
+        assert!(library.videos[&directory.join("empty.mp4")]
+            .inode_number
+            .is_none());
+    }
+
+    #[test]
+    fn file_version_changes_with_size() {
+        let location =
+            std::env::temp_dir().join(format!("video-library-version-{}.mp4", std::process::id()));
+        std::fs::write(&location, b"a").unwrap();
+        let file_version = FileVersion::of(&location);
+        std::fs::write(&location, b"ab").unwrap();
+        let changed_file_version = FileVersion::of(&location);
+        std::fs::remove_file(&location).unwrap();
+
+        assert!(file_version.is_some());
+        assert_ne!(file_version, changed_file_version);
+        assert_eq!(FileVersion::of(&location), None);
+    }
+
+    #[test]
+    fn find_videos_when_file() {
+        assert_eq!(
+            find_videos(&["Cargo.toml".to_string()]),
+            vec![PathBuf::from("Cargo.toml")]
+        );
+    }
+
+    #[test]
+    fn find_videos_when_not_exist() {
+        assert!(find_videos(&["does-not-exist.mp4".to_string()]).is_empty());
+    }
+}
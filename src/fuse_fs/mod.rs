@@ -1,11 +1,52 @@
 use crate::fuse_fs::fs::VideoFileSystem;
+use crate::fuse_fs::library::VideoLibrary;
+use crate::fuse_fs::models::file::FileInformation;
+use crate::fuse_fs::models::nodes::{FuseNodeStore, ROOT_INODE_NUMBER};
+pub use crate::fuse_fs::models::state::StateFile;
+pub use crate::fuse_fs::models::views::PipelineViewDefinition;
 use crate::fuse_fs::nodes::create_default_video_nodes;
+pub use crate::fuse_fs::nodes::{load_pipeline_views, ListingMode, VideoNodesOptions};
+use crate::video_processing::cache::FRAME_CACHE;
+use std::path::Path;
 
 pub(crate) mod fs;
+mod library;
 mod models;
 mod nodes;
 
-pub fn create_video_filesystem(video_location: &str) -> VideoFileSystem {
-    let nodes = create_default_video_nodes(video_location);
-    VideoFileSystem { nodes }
+const CACHE_STATISTICS_FILE_NAME: &str = "cache-statistics.yml";
+
+pub fn create_video_filesystem(
+    video_locations: &[String],
+    options: &VideoNodesOptions,
+    number_of_workers: usize,
+) -> Result<VideoFileSystem<'static>, String> {
+    // A single video file is mounted at the root, whereas directories of videos (or multiple
+    // videos) are mounted with a subtree per video
+    if video_locations.len() == 1 && Path::new(&video_locations[0]).is_file() {
+        let mut nodes = create_default_video_nodes(&video_locations[0], options)?;
+        insert_cache_statistics_file(&mut nodes);
+        return Ok(VideoFileSystem::new(nodes, None, number_of_workers));
+    }
+
+    let mut nodes = FuseNodeStore::new();
+    let mut library = VideoLibrary::new(video_locations, options);
+    library.refresh(&mut nodes);
+    insert_cache_statistics_file(&mut nodes);
+    Ok(VideoFileSystem::new(
+        nodes,
+        Some(library),
+        number_of_workers,
+    ))
+}
+
+// Note: the file is never listed (even once read), so that the root only lists the mounted content
+fn insert_cache_statistics_file(node_store: &mut FuseNodeStore) {
+    node_store.create_and_insert_file(
+        FileInformation::new_hidden(
+            CACHE_STATISTICS_FILE_NAME,
+            Box::new(|| FRAME_CACHE.get_statistics().to_yaml().unwrap().into_bytes()),
+        ),
+        ROOT_INODE_NUMBER,
+    );
 }
@@ -0,0 +1,132 @@
+use std::cmp::{max, min};
+use std::collections::HashMap;
+
+// Data of a file, held for an open file handle. Files opened for reading are read from a snapshot of
+// their data, so reads are consistent even if the file changes whilst open. Data written to a file
+// is only applied to the file when the handle is flushed or released, so that files are not changed
+// with partially written data.
+pub struct FileBuffer {
+    pub inode_number: u64,
+    pub data: Vec<u8>,
+    pub writable: bool,
+    // Whether the data has changed since it was last applied
+    pub modified: bool,
+}
+
+impl FileBuffer {
+    pub fn new(inode_number: u64, data: Vec<u8>, writable: bool) -> Self {
+        FileBuffer {
+            inode_number,
+            data,
+            writable,
+            modified: false,
+        }
+    }
+
+    pub fn read(&self, offset: usize, size: usize) -> &[u8] {
+        let start_position = min(offset, self.data.len());
+        let end_position = min(offset.saturating_add(size), self.data.len());
+        &self.data[start_position..end_position]
+    }
+
+    pub fn write(&mut self, offset: usize, data: &[u8]) {
+        let end_position = offset + data.len();
+        self.data.resize(max(self.data.len(), end_position), 0);
+        self.data[offset..end_position].copy_from_slice(data);
+        self.modified = true;
+    }
+
+    pub fn truncate(&mut self, size: usize) {
+        self.data.resize(size, 0);
+        self.modified = true;
+    }
+}
+
+// Buffers of open file handles, keyed by file handle
+#[derive(Default)]
+pub struct FileBuffers {
+    buffers: HashMap<u64, FileBuffer>,
+    next_file_handle: u64,
+}
+
+impl FileBuffers {
+    pub fn new() -> Self {
+        FileBuffers {
+            next_file_handle: 1,
+            ..Default::default()
+        }
+    }
+
+    // Opens a buffer for writing to the given file, which starts with the file's current data,
+    // returning the buffer's file handle
+    pub fn open(&mut self, inode_number: u64, data: Vec<u8>) -> u64 {
+        self.insert(FileBuffer::new(inode_number, data, true))
+    }
+
+    // Opens a buffer for reading the given snapshot of a file's data, returning the buffer's file
+    // handle
+    pub fn open_snapshot(&mut self, inode_number: u64, data: Vec<u8>) -> u64 {
+        self.insert(FileBuffer::new(inode_number, data, false))
+    }
+
+    pub fn get(&self, file_handle: u64) -> Option<&FileBuffer> {
+        self.buffers.get(&file_handle)
+    }
+
+    pub fn get_mut(&mut self, file_handle: u64) -> Option<&mut FileBuffer> {
+        self.buffers.get_mut(&file_handle)
+    }
+
+    pub fn remove(&mut self, file_handle: u64) -> Option<FileBuffer> {
+        self.buffers.remove(&file_handle)
+    }
+
+    fn insert(&mut self, buffer: FileBuffer) -> u64 {
+        let file_handle = self.next_file_handle;
+        self.next_file_handle += 1;
+        self.buffers.insert(file_handle, buffer);
+        file_handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, b"xy", b"xyc"; "when overwriting start")]
+    #[test_case(3, b"de", b"abcde"; "when appending")]
+    #[test_case(5, b"f", b"abc\0\0f"; "when writing after end")]
+    fn write(offset: usize, data: &[u8], expected: &[u8]) {
+        let mut buffer = FileBuffer::new(1, b"abc".to_vec(), true);
+        assert!(!buffer.modified);
+        buffer.write(offset, data);
+        assert_eq!(buffer.data, expected);
+        assert!(buffer.modified);
+    }
+
+    #[test_case(0, 2, b"ab"; "when start")]
+    #[test_case(1, 5, b"bc"; "when beyond end")]
+    #[test_case(4, 1, b""; "when after end")]
+    fn read(offset: usize, size: usize, expected: &[u8]) {
+        let buffer = FileBuffer::new(1, b"abc".to_vec(), false);
+        assert_eq!(buffer.read(offset, size), expected);
+    }
+
+    #[test_case(1, b"a"; "when shortening")]
+    #[test_case(5, b"abc\0\0"; "when extending")]
+    fn truncate(size: usize, expected: &[u8]) {
+        let mut buffer = FileBuffer::new(1, b"abc".to_vec(), true);
+        buffer.truncate(size);
+        assert_eq!(buffer.data, expected);
+        assert!(buffer.modified);
+    }
+
+    #[test]
+    fn file_handles_are_unique() {
+        let mut file_buffers = FileBuffers::new();
+        let file_handle = file_buffers.open(1, vec![]);
+        file_buffers.remove(file_handle);
+        assert_ne!(file_buffers.open(1, vec![]), file_handle);
+    }
+}
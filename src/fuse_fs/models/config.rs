@@ -1,9 +1,48 @@
+use crate::video_processing::edges::{
+    get_valid_kernel_sizes, EdgeOperator, EdgeParameters, SobelDirection,
+};
+use crate::video_processing::encoding::EncodingConfiguration;
+use crate::video_processing::pipeline::PipelineOperation;
+use crate::video_processing::resize::{Fit, Interpolation};
+use crate::video_processing::threshold::{ThresholdMethod, ThresholdParameters};
+use crate::video_processing::Region;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
 
 #[derive(Clone, Debug)]
 pub enum ConfigurationHolder {
-    None,
+    Basic(BasicConfiguration),
     BlackAndWhite(BlackAndWhiteConfiguration),
+    Cropped(CroppedConfiguration),
+    Resized(ResizedConfiguration),
+    Edges(EdgesConfiguration),
+    Pipeline(PipelineConfiguration),
+}
+
+impl ConfigurationHolder {
+    pub fn get_encoding(&self) -> &EncodingConfiguration {
+        match self {
+            ConfigurationHolder::Basic(x) => &x.encoding,
+            ConfigurationHolder::BlackAndWhite(x) => &x.encoding,
+            ConfigurationHolder::Cropped(x) => &x.encoding,
+            ConfigurationHolder::Resized(x) => &x.encoding,
+            ConfigurationHolder::Edges(x) => &x.encoding,
+            ConfigurationHolder::Pipeline(x) => &x.encoding,
+        }
+    }
+
+    pub fn to_yaml(&self) -> Result<String, String> {
+        match self {
+            ConfigurationHolder::Basic(x) => x.to_yaml(),
+            ConfigurationHolder::BlackAndWhite(x) => x.to_yaml(),
+            ConfigurationHolder::Cropped(x) => x.to_yaml(),
+            ConfigurationHolder::Resized(x) => x.to_yaml(),
+            ConfigurationHolder::Edges(x) => x.to_yaml(),
+            ConfigurationHolder::Pipeline(x) => x.to_yaml(),
+        }
+    }
 }
 
 pub trait Configuration {
@@ -22,38 +61,899 @@ pub trait Configuration {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct BlackAndWhiteConfiguration {
+// Configuration of views that have no configuration of their own, other than how their images are
+// encoded
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BasicConfiguration {
+    #[serde(default)]
+    pub(crate) encoding: EncodingConfiguration,
+}
+
+impl Configuration for BasicConfiguration {
+    fn from_yaml(yaml_string: &str) -> Result<Self, String> {
+        let configuration = parse_yaml::<BasicConfiguration>(yaml_string)?;
+        configuration.encoding.validate()?;
+        Ok(configuration)
+    }
+}
+
+pub const DEFAULT_ADAPTIVE_BLOCK_SIZE: u32 = 11;
+pub const DEFAULT_ADAPTIVE_C: f64 = 2.0;
+
+// How a (greyscale) frame is thresholded to black and white
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ThresholdConfiguration {
+    // Defaults to `global` if a threshold is given, else `otsu`
+    #[serde(default)]
+    pub(crate) method: Option<ThresholdMethod>,
+    #[serde(default)]
     pub(crate) threshold: Option<u8>,
+    // Only used by the adaptive methods
+    #[serde(default)]
+    pub(crate) block_size: Option<u32>,
+    #[serde(default)]
+    pub(crate) c: Option<f64>,
+    #[serde(default)]
+    pub(crate) invert: bool,
+}
+
+impl ThresholdConfiguration {
+    // Gets the parameters to threshold frames with, erroring if the configuration's parameters do
+    // not apply to its method
+    pub fn get_threshold_parameters(&self) -> Result<ThresholdParameters, String> {
+        let method = match (self.method, self.threshold) {
+            (Some(x), _) => x,
+            (None, Some(_)) => ThresholdMethod::Global,
+            (None, None) => ThresholdMethod::Otsu,
+        };
+        let threshold = match (method, self.threshold) {
+            (ThresholdMethod::Global, Some(x)) => x,
+            (ThresholdMethod::Global, None) => {
+                return Err("A threshold is required by the global method".to_string())
+            }
+            (_, Some(_)) => {
+                return Err(format!(
+                    "A threshold cannot be given with the {} method, as it calculates its own",
+                    method
+                ))
+            }
+            (_, None) => 0,
+        };
+
+        if !method.is_adaptive() && (self.block_size.is_some() || self.c.is_some()) {
+            return Err(format!(
+                "block_size and c are only used by the adaptive methods, not {}",
+                method
+            ));
+        }
+        let block_size = self.block_size.unwrap_or(DEFAULT_ADAPTIVE_BLOCK_SIZE);
+        if block_size < 3 || block_size % 2 != 1 {
+            return Err(format!(
+                "block_size must be an odd number greater than 1: {}",
+                block_size
+            ));
+        }
+        let c = self.c.unwrap_or(DEFAULT_ADAPTIVE_C);
+        if !c.is_finite() {
+            return Err(format!("c must be a finite number: {}", c));
+        }
+
+        Ok(ThresholdParameters {
+            method,
+            threshold,
+            block_size,
+            c,
+            invert: self.invert,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BlackAndWhiteConfiguration {
+    #[serde(flatten)]
+    pub(crate) thresholding: ThresholdConfiguration,
+    #[serde(default)]
+    pub(crate) encoding: EncodingConfiguration,
+    #[serde(flatten, skip_serializing)]
+    unknown_fields: UnknownFields,
 }
 
 impl Configuration for BlackAndWhiteConfiguration {
     fn from_yaml(yaml_string: &str) -> Result<Self, String> {
-        match serde_yaml::from_str::<BlackAndWhiteConfiguration>(&yaml_string) {
-            Ok(x) => Ok(x),
-            Err(e) => Err(e.to_string()),
+        let configuration = parse_yaml::<BlackAndWhiteConfiguration>(yaml_string)?;
+        reject_unknown_fields(&configuration.unknown_fields)?;
+        configuration.thresholding.get_threshold_parameters()?;
+        configuration.encoding.validate()?;
+        Ok(configuration)
+    }
+}
+
+// A position or length in a frame, either in pixels (integers, e.g. `100`) or as a fraction of the
+// frame's size (decimals, e.g. `0.5`)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum FrameDimension {
+    Pixels(u64),
+    Fraction(f64),
+}
+
+impl FrameDimension {
+    pub fn to_pixels(self, frame_size: u64) -> Result<u64, String> {
+        match self {
+            FrameDimension::Pixels(x) => Ok(x),
+            FrameDimension::Fraction(x) => {
+                if !(0.0..=1.0).contains(&x) {
+                    return Err(format!("Fraction must be between 0 and 1: {}", x));
+                }
+                Ok((x * frame_size as f64).round() as u64)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CroppedConfiguration {
+    pub(crate) x: FrameDimension,
+    pub(crate) y: FrameDimension,
+    pub(crate) width: FrameDimension,
+    pub(crate) height: FrameDimension,
+    pub(crate) encoding: EncodingConfiguration,
+}
+
+impl CroppedConfiguration {
+    // Gets the region of a frame of the given size to crop to, erroring if the region is not
+    // within the frame
+    pub fn get_region(&self, frame_width: u64, frame_height: u64) -> Result<Region, String> {
+        let region = Region {
+            x: self.x.to_pixels(frame_width)?,
+            y: self.y.to_pixels(frame_height)?,
+            width: self.width.to_pixels(frame_width)?,
+            height: self.height.to_pixels(frame_height)?,
+        };
+        if region.width == 0 || region.height == 0 {
+            return Err(format!(
+                "Crop must have a width and height: {}x{}",
+                region.width, region.height
+            ));
+        }
+        // Note: a region whose end cannot be represented is outside of any frame
+        let is_within = |start: u64, length: u64, frame_size: u64| matches!(start.checked_add(length), Some(end) if end <= frame_size);
+        if !is_within(region.x, region.width, frame_width)
+            || !is_within(region.y, region.height, frame_height)
+        {
+            return Err(format!(
+                "Crop of {}x{} at ({}, {}) goes outside of the {}x{} frame",
+                region.width, region.height, region.x, region.y, frame_width, frame_height
+            ));
+        }
+        // Regions are given to OpenCV as 32-bit integers
+        for (name, value) in [
+            ("x", region.x),
+            ("y", region.y),
+            ("width", region.width),
+            ("height", region.height),
+        ] {
+            if value > i32::MAX as u64 {
+                return Err(format!("{} must be at most {}: {}", name, i32::MAX, value));
+            }
+        }
+        Ok(region)
+    }
+}
+
+impl Configuration for CroppedConfiguration {
+    fn from_yaml(yaml_string: &str) -> Result<Self, String> {
+        let configuration = parse_yaml::<CroppedConfiguration>(yaml_string)?;
+        configuration.encoding.validate()?;
+        Ok(configuration)
+    }
+}
+
+impl Default for CroppedConfiguration {
+    fn default() -> Self {
+        CroppedConfiguration {
+            x: FrameDimension::Pixels(0),
+            y: FrameDimension::Pixels(0),
+            width: FrameDimension::Fraction(1.0),
+            height: FrameDimension::Fraction(1.0),
+            encoding: EncodingConfiguration::default(),
         }
     }
 }
 
-impl Default for BlackAndWhiteConfiguration {
+// Note: sizes are limited to stop typos from exhausting memory
+const MAXIMUM_RESIZED_DIMENSION: u64 = 16384;
+
+// The size to scale a frame to
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResizeConfiguration {
+    pub(crate) width: Option<u64>,
+    pub(crate) height: Option<u64>,
+    pub(crate) fit: Fit,
+    pub(crate) interpolation: Interpolation,
+}
+
+impl ResizeConfiguration {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("width", self.width), ("height", self.height)] {
+            if let Some(value) = value {
+                if value == 0 || value > MAXIMUM_RESIZED_DIMENSION {
+                    return Err(format!(
+                        "{} must be between 1 and {}: {}",
+                        name, MAXIMUM_RESIZED_DIMENSION, value
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ResizedConfiguration {
+    #[serde(flatten)]
+    pub(crate) resizing: ResizeConfiguration,
+    pub(crate) encoding: EncodingConfiguration,
+    #[serde(flatten, skip_serializing)]
+    unknown_fields: UnknownFields,
+}
+
+impl Configuration for ResizedConfiguration {
+    fn from_yaml(yaml_string: &str) -> Result<Self, String> {
+        let configuration = parse_yaml::<ResizedConfiguration>(yaml_string)?;
+        reject_unknown_fields(&configuration.unknown_fields)?;
+        configuration.resizing.validate()?;
+        configuration.encoding.validate()?;
+        Ok(configuration)
+    }
+}
+
+// Note: processing with large kernels is slow (and tends to leave little of the frame)
+const MAXIMUM_KERNEL_SIZE: u32 = 99;
+
+// How edges are detected in a (greyscale) frame
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EdgeDetectionConfiguration {
+    pub(crate) operator: EdgeOperator,
+    // Only used by the canny operator
+    pub(crate) low_threshold: f64,
+    pub(crate) high_threshold: f64,
+    pub(crate) kernel_size: u32,
+    // Only used by the sobel operator
+    pub(crate) direction: SobelDirection,
+    // Size of the Gaussian kernel to blur the frame with beforehand (odd), which reduces the
+    // edges found from noise
+    pub(crate) blur: Option<u32>,
+}
+
+impl EdgeDetectionConfiguration {
+    pub fn get_edge_parameters(&self) -> Result<EdgeParameters, String> {
+        if self.low_threshold < 0.0 || self.low_threshold > self.high_threshold {
+            return Err(format!(
+                "low_threshold must be between 0 and high_threshold ({}): {}",
+                self.high_threshold, self.low_threshold
+            ));
+        }
+        let valid_kernel_sizes = get_valid_kernel_sizes(self.operator);
+        if !valid_kernel_sizes.contains(&self.kernel_size) {
+            return Err(format!(
+                "kernel_size must be one of {:?} for the {} operator: {}",
+                valid_kernel_sizes, self.operator, self.kernel_size
+            ));
+        }
+        if let Some(blur) = self.blur {
+            validate_kernel_size("blur", blur)?;
+        }
+        Ok(EdgeParameters {
+            operator: self.operator,
+            low_threshold: self.low_threshold,
+            high_threshold: self.high_threshold,
+            kernel_size: self.kernel_size,
+            direction: self.direction,
+            blur: self.blur,
+        })
+    }
+}
+
+impl Default for EdgeDetectionConfiguration {
+    fn default() -> Self {
+        EdgeDetectionConfiguration {
+            operator: EdgeOperator::default(),
+            low_threshold: 100.0,
+            high_threshold: 200.0,
+            kernel_size: 3,
+            direction: SobelDirection::default(),
+            blur: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct EdgesConfiguration {
+    #[serde(flatten)]
+    pub(crate) edge_detection: EdgeDetectionConfiguration,
+    pub(crate) encoding: EncodingConfiguration,
+    #[serde(flatten, skip_serializing)]
+    unknown_fields: UnknownFields,
+}
+
+impl Configuration for EdgesConfiguration {
+    fn from_yaml(yaml_string: &str) -> Result<Self, String> {
+        let configuration = parse_yaml::<EdgesConfiguration>(yaml_string)?;
+        reject_unknown_fields(&configuration.unknown_fields)?;
+        configuration.edge_detection.get_edge_parameters()?;
+        configuration.encoding.validate()?;
+        Ok(configuration)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GaussianBlurConfiguration {
+    pub(crate) kernel_size: u32,
+    // Standard deviation of the Gaussian (0 to calculate it from the kernel size)
+    pub(crate) sigma: f64,
+}
+
+impl Default for GaussianBlurConfiguration {
+    fn default() -> Self {
+        GaussianBlurConfiguration {
+            kernel_size: 5,
+            sigma: 0.0,
+        }
+    }
+}
+
+// Note: iterations are limited as each one is a pass over the frame
+const MAXIMUM_MORPHOLOGY_ITERATIONS: u32 = 100;
+
+// Configuration of dilation and erosion
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MorphologyConfiguration {
+    pub(crate) kernel_size: u32,
+    pub(crate) iterations: u32,
+}
+
+impl Default for MorphologyConfiguration {
     fn default() -> Self {
-        BlackAndWhiteConfiguration { threshold: None }
+        MorphologyConfiguration {
+            kernel_size: 3,
+            iterations: 1,
+        }
+    }
+}
+
+impl MorphologyConfiguration {
+    fn validate(&self) -> Result<(), String> {
+        validate_kernel_size("kernel_size", self.kernel_size)?;
+        if self.iterations == 0 || self.iterations > MAXIMUM_MORPHOLOGY_ITERATIONS {
+            return Err(format!(
+                "iterations must be between 1 and {}: {}",
+                MAXIMUM_MORPHOLOGY_ITERATIONS, self.iterations
+            ));
+        }
+        Ok(())
+    }
+}
+
+// A step of a pipeline. Note: steps that need a greyscale frame convert colour frames themselves.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PipelineStep {
+    Greyscale,
+    GaussianBlur(GaussianBlurConfiguration),
+    Threshold(ThresholdConfiguration),
+    Edges(EdgeDetectionConfiguration),
+    Dilate(MorphologyConfiguration),
+    Erode(MorphologyConfiguration),
+    Resize(ResizeConfiguration),
+}
+
+impl PipelineStep {
+    pub fn to_operation(&self) -> Result<PipelineOperation, String> {
+        Ok(match self {
+            PipelineStep::Greyscale => PipelineOperation::Greyscale,
+            PipelineStep::GaussianBlur(x) => {
+                validate_kernel_size("kernel_size", x.kernel_size)?;
+                if !x.sigma.is_finite() || x.sigma < 0.0 {
+                    return Err(format!("sigma must not be negative: {}", x.sigma));
+                }
+                PipelineOperation::GaussianBlur {
+                    kernel_size: x.kernel_size,
+                    sigma: x.sigma,
+                }
+            }
+            PipelineStep::Threshold(x) => {
+                PipelineOperation::Threshold(x.get_threshold_parameters()?)
+            }
+            PipelineStep::Edges(x) => PipelineOperation::Edges(x.get_edge_parameters()?),
+            PipelineStep::Dilate(x) => {
+                x.validate()?;
+                PipelineOperation::Dilate {
+                    kernel_size: x.kernel_size,
+                    iterations: x.iterations,
+                }
+            }
+            PipelineStep::Erode(x) => {
+                x.validate()?;
+                PipelineOperation::Erode {
+                    kernel_size: x.kernel_size,
+                    iterations: x.iterations,
+                }
+            }
+            PipelineStep::Resize(x) => {
+                x.validate()?;
+                PipelineOperation::Resize {
+                    size: (x.width, x.height),
+                    fit: x.fit,
+                    interpolation: x.interpolation,
+                }
+            }
+        })
+    }
+}
+
+// Configuration of a view that applies a pipeline of steps to frames, in order
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineConfiguration {
+    // Note: steps are written as `- <step>: {<parameters>}`, or `- <step>` if they have none
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    pub(crate) steps: Vec<PipelineStep>,
+    pub(crate) encoding: EncodingConfiguration,
+}
+
+impl PipelineConfiguration {
+    pub fn get_operations(&self) -> Result<Vec<PipelineOperation>, String> {
+        self.steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| {
+                step.to_operation()
+                    .map_err(|e| format!("Invalid step {} of pipeline: {}", i + 1, e))
+            })
+            .collect()
+    }
+}
+
+impl Configuration for PipelineConfiguration {
+    fn from_yaml(yaml_string: &str) -> Result<Self, String> {
+        let configuration = parse_yaml::<PipelineConfiguration>(yaml_string)?;
+        configuration.get_operations()?;
+        configuration.encoding.validate()?;
+        Ok(configuration)
+    }
+}
+
+// Validates the size of a (square) kernel that must have a centre pixel
+fn validate_kernel_size(name: &str, kernel_size: u32) -> Result<(), String> {
+    if kernel_size % 2 != 1 || kernel_size > MAXIMUM_KERNEL_SIZE {
+        return Err(format!(
+            "{} must be an odd number no greater than {}: {}",
+            name, MAXIMUM_KERNEL_SIZE, kernel_size
+        ));
+    }
+    Ok(())
+}
+
+// Note: an empty document is taken to be the default configuration
+fn parse_yaml<T: DeserializeOwned + Default>(yaml_string: &str) -> Result<T, String> {
+    if yaml_string.trim().is_empty() {
+        return Ok(T::default());
+    }
+    match serde_yaml::from_str::<T>(yaml_string) {
+        Ok(x) => Ok(x),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Fields of a configuration that were not recognised. Note: `deny_unknown_fields` cannot be used
+// with `flatten`, so configurations with flattened fields collect what is left over into this
+// instead (which must be their last field), then reject it if it is not empty.
+type UnknownFields = BTreeMap<String, serde_yaml::Value>;
+
+fn reject_unknown_fields(unknown_fields: &UnknownFields) -> Result<(), String> {
+    if unknown_fields.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<&str> = unknown_fields.keys().map(String::as_str).collect();
+    Err(format!("Unknown fields: {}", names.join(", ")))
+}
+
+// Where the configuration used for a frame's view came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigurationSource {
+    // Set for the frame
+    Frame,
+    // Set for the view of every frame in the video
+    Video,
+    // The view's default
+    Default,
+}
+
+// Configurations that have been set for views of frames, keyed by frame number and view name, along
+// with the video-wide configurations of views that frames inherit (keyed by view name)
+pub struct ConfigurationStore {
+    configurations: RwLock<HashMap<(u64, String), ConfigurationHolder>>,
+    video_configurations: RwLock<HashMap<String, ConfigurationHolder>>,
+}
+
+impl ConfigurationStore {
+    pub fn new() -> Self {
+        ConfigurationStore {
+            configurations: Default::default(),
+            video_configurations: Default::default(),
+        }
+    }
+
+    // Gets the configuration used for a frame's view, which is inherited from the video-wide
+    // configuration of the view (or the given default) if not set for the frame
+    pub fn get_effective(
+        &self,
+        frame_number: u64,
+        view_name: &str,
+        default_configuration: &ConfigurationHolder,
+    ) -> (ConfigurationHolder, ConfigurationSource) {
+        if let Some(configuration) = self.get(frame_number, view_name) {
+            return (configuration, ConfigurationSource::Frame);
+        }
+        match self.get_video(view_name) {
+            Some(configuration) => (configuration, ConfigurationSource::Video),
+            None => (default_configuration.clone(), ConfigurationSource::Default),
+        }
+    }
+
+    pub fn get_video(&self, view_name: &str) -> Option<ConfigurationHolder> {
+        self.video_configurations
+            .read()
+            .unwrap()
+            .get(view_name)
+            .cloned()
+    }
+
+    pub fn set_video(&self, view_name: &str, configuration: ConfigurationHolder) {
+        self.video_configurations
+            .write()
+            .unwrap()
+            .insert(view_name.to_string(), configuration);
+    }
+
+    pub fn remove_video(&self, view_name: &str) {
+        self.video_configurations.write().unwrap().remove(view_name);
+    }
+
+    pub fn get(&self, frame_number: u64, view_name: &str) -> Option<ConfigurationHolder> {
+        self.configurations
+            .read()
+            .unwrap()
+            .get(&(frame_number, view_name.to_string()))
+            .cloned()
+    }
+
+    pub fn set(&self, frame_number: u64, view_name: &str, configuration: ConfigurationHolder) {
+        self.configurations
+            .write()
+            .unwrap()
+            .insert((frame_number, view_name.to_string()), configuration);
+    }
+
+    // Removes the configuration set for a frame's view, which then inherits its configuration again
+    pub fn remove(&self, frame_number: u64, view_name: &str) {
+        self.configurations
+            .write()
+            .unwrap()
+            .remove(&(frame_number, view_name.to_string()));
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use indoc::indoc;
     use test_case::test_case;
 
     #[test_case(None; "when threshold is none")]
     #[test_case(Some(0); "when threshold is 0")]
     #[test_case(Some(255); "when threshold is 255")]
     fn black_and_white_configuration_serialisation(threshold: Option<u8>) {
-        let configuration = BlackAndWhiteConfiguration { threshold };
+        let configuration = BlackAndWhiteConfiguration {
+            thresholding: ThresholdConfiguration {
+                threshold,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
         let yaml = configuration.to_yaml().unwrap();
         let parsed_configuration = BlackAndWhiteConfiguration::from_yaml(&yaml).unwrap();
         assert_eq!(parsed_configuration, configuration);
     }
+
+    #[test_case("", Ok((ThresholdMethod::Otsu, 0)); "when default")]
+    #[test_case("threshold: 100", Ok((ThresholdMethod::Global, 100)); "when only threshold")]
+    #[test_case("{method: global, threshold: 100}", Ok((ThresholdMethod::Global, 100)); "when global")]
+    #[test_case("method: triangle", Ok((ThresholdMethod::Triangle, 0)); "when triangle")]
+    #[test_case("{method: adaptive-mean, block_size: 21, c: -1.5}", Ok((ThresholdMethod::AdaptiveMean, 0)); "when adaptive")]
+    #[test_case("method: global", Err(()); "when global without threshold")]
+    #[test_case("{method: otsu, threshold: 100}", Err(()); "when otsu with threshold")]
+    #[test_case("{method: adaptive-gaussian, threshold: 100}", Err(()); "when adaptive with threshold")]
+    #[test_case("{method: otsu, block_size: 11}", Err(()); "when block size without adaptive")]
+    #[test_case("{threshold: 100, c: 2}", Err(()); "when c without adaptive")]
+    #[test_case("{method: adaptive-mean, block_size: 10}", Err(()); "when block size even")]
+    #[test_case("{method: adaptive-mean, block_size: 1}", Err(()); "when block size too small")]
+    #[test_case("method: adaptive", Err(()); "when unknown method")]
+    #[test_case("treshold: 100", Err(()); "when unknown field")]
+    fn black_and_white_configuration_method(
+        yaml: &str,
+        expected: Result<(ThresholdMethod, u8), ()>,
+    ) {
+        let parameters = BlackAndWhiteConfiguration::from_yaml(yaml)
+            .map(|x| x.thresholding.get_threshold_parameters().unwrap())
+            .map(|x| (x.method, x.threshold))
+            .map_err(|_| ());
+        assert_eq!(parameters, expected);
+    }
+
+    #[test]
+    fn black_and_white_configuration_adaptive_defaults() {
+        let parameters =
+            BlackAndWhiteConfiguration::from_yaml("{method: adaptive-gaussian, invert: true}")
+                .unwrap()
+                .thresholding
+                .get_threshold_parameters()
+                .unwrap();
+        assert_eq!(parameters.block_size, DEFAULT_ADAPTIVE_BLOCK_SIZE);
+        assert_eq!(parameters.c, DEFAULT_ADAPTIVE_C);
+        assert!(parameters.invert);
+    }
+
+    #[test]
+    fn black_and_white_configuration_with_encoding() {
+        let configuration = BlackAndWhiteConfiguration::from_yaml(
+            "threshold: 10\nencoding:\n  jpg:\n    quality: 95\n",
+        )
+        .unwrap();
+        assert_eq!(configuration.thresholding.threshold, Some(10));
+        assert_eq!(configuration.encoding.jpg.unwrap().quality, 95);
+    }
+
+    #[test_case(""; "when default")]
+    #[test_case("{operator: sobel, kernel_size: 1, direction: x}"; "when sobel")]
+    #[test_case("{operator: laplacian, kernel_size: 5, blur: 3}"; "when laplacian with blur")]
+    #[test_case("{low_threshold: 50, high_threshold: 150}"; "when canny thresholds")]
+    fn edges_configuration_when_valid(yaml: &str) {
+        let configuration = EdgesConfiguration::from_yaml(yaml).unwrap();
+        let yaml = configuration.to_yaml().unwrap();
+        assert_eq!(EdgesConfiguration::from_yaml(&yaml).unwrap(), configuration);
+    }
+
+    #[test_case("{low_threshold: 200, high_threshold: 100}"; "when low threshold above high")]
+    #[test_case("low_threshold: -1"; "when low threshold negative")]
+    #[test_case("kernel_size: 1"; "when canny kernel size too small")]
+    #[test_case("{operator: sobel, kernel_size: 9}"; "when sobel kernel size too large")]
+    #[test_case("blur: 4"; "when blur even")]
+    #[test_case("operator: prewitt"; "when unknown operator")]
+    #[test_case("kernel: 5"; "when unknown field")]
+    fn edges_configuration_when_invalid(yaml: &str) {
+        assert!(EdgesConfiguration::from_yaml(yaml).is_err());
+    }
+
+    #[test]
+    fn pipeline_configuration() {
+        let yaml = indoc! {"
+            steps:
+            - greyscale
+            - gaussian-blur:
+                kernel_size: 3
+            - threshold:
+                method: adaptive-mean
+            - dilate: {}
+        "};
+        let configuration = PipelineConfiguration::from_yaml(yaml).unwrap();
+        let operations = configuration.get_operations().unwrap();
+        assert_eq!(operations.len(), 4);
+        assert_eq!(operations[0], PipelineOperation::Greyscale);
+        assert_eq!(
+            operations[1],
+            PipelineOperation::GaussianBlur {
+                kernel_size: 3,
+                sigma: 0.0
+            }
+        );
+        assert_eq!(
+            operations[3],
+            PipelineOperation::Dilate {
+                kernel_size: 3,
+                iterations: 1
+            }
+        );
+
+        let yaml = configuration.to_yaml().unwrap();
+        assert!(yaml.contains("- greyscale\n"));
+        assert_eq!(
+            PipelineConfiguration::from_yaml(&yaml).unwrap(),
+            configuration
+        );
+    }
+
+    #[test_case("steps: [sharpen]"; "when unknown step")]
+    #[test_case("steps: [{gaussian-blur: {kernel_size: 4}}]"; "when even kernel size")]
+    #[test_case("steps: [{erode: {iterations: 0}}]"; "when no iterations")]
+    #[test_case("steps: [{threshold: {method: global}}]"; "when invalid threshold")]
+    #[test_case("steps: [{resize: {width: 0}}]"; "when invalid resize")]
+    #[test_case("steps: [{gaussian-blur: {kernal_size: 9}}]"; "when misspelt blur parameter")]
+    #[test_case("steps: [{dilate: {iteration: 2}}]"; "when misspelt morphology parameter")]
+    #[test_case("steps: [{threshold: {treshold: 100}}]"; "when misspelt threshold parameter")]
+    #[test_case("steps: [{edges: {operater: sobel}}]"; "when misspelt edges parameter")]
+    #[test_case("steps: [{resize: {widht: 64}}]"; "when misspelt resize parameter")]
+    fn pipeline_configuration_when_invalid(yaml: &str) {
+        assert!(PipelineConfiguration::from_yaml(yaml).is_err());
+    }
+
+    #[test_case(""; "when empty")]
+    #[test_case("encoding: {}"; "when no encoding parameters")]
+    fn basic_configuration_when_default(yaml: &str) {
+        assert_eq!(
+            BasicConfiguration::from_yaml(yaml).unwrap(),
+            BasicConfiguration::default()
+        );
+    }
+
+    #[test_case("encoding: {png: {compression: 10}}"; "when invalid encoding parameter")]
+    #[test_case("encoding: {tiff: {}}"; "when unknown image type")]
+    #[test_case("encodnig: {png: {compression: 1}}"; "when unknown field")]
+    fn basic_configuration_when_invalid(yaml: &str) {
+        assert!(BasicConfiguration::from_yaml(yaml).is_err());
+    }
+
+    #[test_case("", Ok((0, 0, 1920, 1080)); "when default")]
+    #[test_case("{x: 10, y: 20, width: 100, height: 50}", Ok((10, 20, 100, 50)); "when pixels")]
+    #[test_case("{x: 0.5, y: 0.5, width: 0.5, height: 0.25}", Ok((960, 540, 960, 270)); "when fractions")]
+    #[test_case("{x: 1820, width: 100}", Ok((1820, 0, 100, 1080)); "when at edge")]
+    #[test_case("{x: 1821, width: 100}", Err("Crop of 100x1080 at (1821, 0) goes outside of the 1920x1080 frame".to_string()); "when outside frame")]
+    #[test_case("{width: 1.5}", Err("Fraction must be between 0 and 1: 1.5".to_string()); "when fraction too large")]
+    #[test_case("{height: 0}", Err("Crop must have a width and height: 1920x0".to_string()); "when empty")]
+    #[test_case("{x: 18446744073709551615, width: 1}", Err("Crop of 1x1080 at (18446744073709551615, 0) goes outside of the 1920x1080 frame".to_string()); "when end overflows")]
+    fn cropped_configuration_region(yaml: &str, expected: Result<(u64, u64, u64, u64), String>) {
+        let configuration = CroppedConfiguration::from_yaml(yaml).unwrap();
+        assert_eq!(
+            configuration.get_region(1920, 1080),
+            expected.map(|(x, y, width, height)| Region {
+                x,
+                y,
+                width,
+                height
+            })
+        );
+    }
+
+    #[test_case("widht: 100"; "when misspelt field")]
+    #[test_case("{x: 10, left: 10}"; "when unknown field")]
+    fn cropped_configuration_unknown_fields(yaml: &str) {
+        assert!(CroppedConfiguration::from_yaml(yaml).is_err());
+    }
+
+    #[test]
+    fn cropped_configuration_region_when_too_large_for_opencv() {
+        let configuration = CroppedConfiguration::from_yaml("{x: 2147483648, width: 1}").unwrap();
+        assert_eq!(
+            configuration.get_region(4294967296, 1080),
+            Err("x must be at most 2147483647: 2147483648".to_string())
+        );
+    }
+
+    #[test]
+    fn cropped_configuration_serialisation() {
+        let configuration = CroppedConfiguration {
+            x: FrameDimension::Pixels(10),
+            y: FrameDimension::Fraction(0.5),
+            ..Default::default()
+        };
+        let yaml = configuration.to_yaml().unwrap();
+        assert_eq!(
+            CroppedConfiguration::from_yaml(&yaml).unwrap(),
+            configuration
+        );
+    }
+
+    #[test]
+    fn resized_configuration() {
+        let configuration =
+            ResizedConfiguration::from_yaml("width: 320\nfit: cover\ninterpolation: area\n")
+                .unwrap();
+        assert_eq!(
+            configuration,
+            ResizedConfiguration {
+                resizing: ResizeConfiguration {
+                    width: Some(320),
+                    height: None,
+                    fit: Fit::Cover,
+                    interpolation: Interpolation::Area,
+                },
+                encoding: EncodingConfiguration::default(),
+                ..Default::default()
+            }
+        );
+        let yaml = configuration.to_yaml().unwrap();
+        assert_eq!(
+            ResizedConfiguration::from_yaml(&yaml).unwrap(),
+            configuration
+        );
+    }
+
+    #[test_case("width: 0"; "when zero width")]
+    #[test_case("height: 100000"; "when height too large")]
+    #[test_case("fit: fill"; "when unknown fit")]
+    #[test_case("{width: 64, heigth: 64}"; "when unknown field")]
+    fn resized_configuration_when_invalid(yaml: &str) {
+        assert!(ResizedConfiguration::from_yaml(yaml).is_err());
+    }
+
+    #[test]
+    fn configuration_store() {
+        let configuration_store = ConfigurationStore::new();
+        assert!(configuration_store.get(1, "test").is_none());
+
+        let configuration = BlackAndWhiteConfiguration {
+            thresholding: ThresholdConfiguration {
+                threshold: Some(42),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        configuration_store.set(
+            1,
+            "test",
+            ConfigurationHolder::BlackAndWhite(configuration.clone()),
+        );
+        match configuration_store.get(1, "test").unwrap() {
+            ConfigurationHolder::BlackAndWhite(x) => assert_eq!(x, configuration),
+            _ => panic!("Unexpected configuration type"),
+        }
+        assert!(configuration_store.get(2, "test").is_none());
+        assert!(configuration_store.get(1, "other").is_none());
+    }
+
+    #[test]
+    fn configuration_store_inheritance() {
+        let configuration_store = ConfigurationStore::new();
+        let default_configuration =
+            ConfigurationHolder::BlackAndWhite(BlackAndWhiteConfiguration::default());
+        let get_threshold = |frame_number| {
+            let (configuration, source) =
+                configuration_store.get_effective(frame_number, "test", &default_configuration);
+            match configuration {
+                ConfigurationHolder::BlackAndWhite(x) => (x.thresholding.threshold, source),
+                _ => panic!("Unexpected configuration type"),
+            }
+        };
+        let create_configuration = |threshold| {
+            ConfigurationHolder::BlackAndWhite(BlackAndWhiteConfiguration {
+                thresholding: ThresholdConfiguration {
+                    threshold: Some(threshold),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        };
+        assert_eq!(get_threshold(1), (None, ConfigurationSource::Default));
+
+        configuration_store.set_video("test", create_configuration(42));
+        configuration_store.set(2, "test", create_configuration(24));
+        assert_eq!(get_threshold(1), (Some(42), ConfigurationSource::Video));
+        assert_eq!(get_threshold(2), (Some(24), ConfigurationSource::Frame));
+        assert!(configuration_store.get_video("other").is_none());
+
+        configuration_store.remove(2, "test");
+        assert_eq!(get_threshold(2), (Some(42), ConfigurationSource::Video));
+        configuration_store.remove_video("test");
+        assert_eq!(get_threshold(2), (None, ConfigurationSource::Default));
+    }
 }
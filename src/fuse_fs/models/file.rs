@@ -1,27 +1,44 @@
+use std::sync::Arc;
+
+// Handles data written to a file, rejecting it if invalid
+pub type DataChangeHandler = dyn Fn(&str) -> Result<(), String>;
+// Gets a file's data, which may be called from any thread (e.g. to produce an image concurrently
+// with other requests)
+pub type DataFetcher = dyn Fn() -> Vec<u8> + Send + Sync;
+// Gets the size of a file's data, if it is known without fetching the data
+pub type SizeFetcher = dyn Fn() -> Option<u64> + Send + Sync;
+
 pub struct FileInformation {
     pub name: String,
-    pub data_fetcher: Option<Box<dyn Fn() -> Vec<u8>>>,
+    pub data_fetcher: Option<Arc<DataFetcher>>,
+    // Used instead of fetching the data to get the size of the file, where fetching the data is
+    // expensive (e.g. encoding an image)
+    pub size_fetcher: Option<Box<SizeFetcher>>,
     pub data: Option<Vec<u8>>,
     pub listed: bool,
+    // Whether the file is never listed, whereas other files that are not listed are listed once
+    // they have been accessed
+    pub hidden: bool,
     pub executable: bool,
     pub writable: bool,
-    on_data_change: Option<Box<dyn Fn(&str) -> Result<(), String>>>,
+    // Whether the file is a temporary file created by a user (e.g. an editor's swap file), which
+    // can be renamed and removed
+    pub scratch: bool,
+    on_data_change: Option<Box<DataChangeHandler>>,
 }
 
 impl FileInformation {
-    pub fn new(
-        name: &str,
-        data_fetcher: Box<dyn Fn() -> Vec<u8>>,
-        listed: bool,
-        executable: bool,
-    ) -> Self {
+    pub fn new(name: &str, data_fetcher: Box<DataFetcher>, listed: bool, executable: bool) -> Self {
         FileInformation {
             name: name.to_string(),
-            data_fetcher: Some(Box::new(data_fetcher)),
+            data_fetcher: Some(Arc::from(data_fetcher)),
+            size_fetcher: None,
             data: None,
             listed,
+            hidden: false,
             executable,
             writable: false,
+            scratch: false,
             on_data_change: None,
         }
     }
@@ -32,21 +49,67 @@ impl FileInformation {
         listed: bool,
         executable: bool,
         writable: bool,
-        on_data_change: Option<Box<dyn Fn(&str) -> Result<(), String>>>,
+        on_data_change: Option<Box<DataChangeHandler>>,
     ) -> Self {
         FileInformation {
             name: name.to_string(),
             data_fetcher: None,
+            size_fetcher: None,
             data: Some(data),
             listed,
+            hidden: false,
             executable,
             writable,
+            scratch: false,
             on_data_change,
         }
     }
 
+    // A file with dynamically generated data that can be changed by writing to it, which is handled
+    // by the given handler (the change is visible in the data that is subsequently generated)
+    pub fn new_writable(
+        name: &str,
+        data_fetcher: Box<DataFetcher>,
+        on_data_change: Box<DataChangeHandler>,
+    ) -> Self {
+        FileInformation {
+            writable: true,
+            on_data_change: Some(on_data_change),
+            ..FileInformation::new(name, data_fetcher, true, false)
+        }
+    }
+
+    pub fn new_scratch(name: &str) -> Self {
+        FileInformation {
+            scratch: true,
+            ..FileInformation::new_with_data(name, vec![], true, false, true, None)
+        }
+    }
+
+    // A file that can be accessed by name, but is never listed
+    pub fn new_hidden(name: &str, data_fetcher: Box<DataFetcher>) -> Self {
+        FileInformation {
+            hidden: true,
+            ..FileInformation::new(name, data_fetcher, false, false)
+        }
+    }
+
+    pub fn with_size_fetcher(mut self, size_fetcher: Box<SizeFetcher>) -> Self {
+        self.size_fetcher = Some(size_fetcher);
+        self
+    }
+
+    // Gets the size of the file's data, which is `None` if it is not known without fetching the
+    // (expensive) data
+    pub fn get_size(&self) -> Option<u64> {
+        match &self.size_fetcher {
+            Some(x) => x(),
+            None => Some(self.get_data().len() as u64),
+        }
+    }
+
     pub fn set_data(&mut self, data: Vec<u8>) -> Result<(), String> {
-        if self.data_fetcher.is_some() {
+        if self.data_fetcher.is_some() && self.on_data_change.is_none() {
             return Err(
                 "Cannot set data as it is dynamically generated with a data fetcher".to_string(),
             );
@@ -58,10 +121,18 @@ impl FileInformation {
         if self.on_data_change.is_some() {
             (self.on_data_change.as_ref().unwrap())(&parsed)?;
         }
-        self.data = Some(data);
+        if self.data_fetcher.is_none() {
+            self.data = Some(data);
+        }
         Ok(())
     }
 
+    // Gets the data fetcher of the file (if its data is not stored), so that the data can be fetched
+    // without borrowing the file
+    pub fn get_data_fetcher(&self) -> Option<Arc<DataFetcher>> {
+        self.data_fetcher.clone()
+    }
+
     pub fn get_data(&self) -> Vec<u8> {
         match &self.data_fetcher {
             None => self.data.as_ref().unwrap().clone(),
@@ -74,6 +145,7 @@ impl FileInformation {
 mod tests {
     use super::*;
     use std::sync::mpsc::channel;
+    use std::sync::RwLock;
 
     #[test]
     fn get_data_when_stored() {
@@ -82,10 +154,13 @@ mod tests {
         let file_information = FileInformation {
             name: "".to_string(),
             data_fetcher: None,
+            size_fetcher: None,
             data: Some(data.clone()),
             listed: false,
+            hidden: false,
             executable: false,
             writable: false,
+            scratch: false,
             on_data_change: None,
         };
         assert_eq!(file_information.get_data(), data);
@@ -93,21 +168,40 @@ mod tests {
 
     #[test]
     fn get_data_when_fetched() {
-        let fetcher = Box::new(|| "DATA".as_bytes().to_vec());
+        let fetcher = || "DATA".as_bytes().to_vec();
         let expected = fetcher();
 
         let file_information = FileInformation {
             name: "".to_string(),
-            data_fetcher: Some(fetcher),
+            data_fetcher: Some(Arc::new(fetcher)),
+            size_fetcher: None,
             data: None,
             listed: false,
+            hidden: false,
             executable: false,
             writable: false,
+            scratch: false,
             on_data_change: None,
         };
         assert_eq!(file_information.get_data(), expected);
     }
 
+    #[test]
+    fn get_size_when_size_fetched() {
+        let file_information = FileInformation::new(
+            "",
+            Box::new(|| panic!("Data should not be fetched")),
+            false,
+            false,
+        )
+        .with_size_fetcher(Box::new(|| None));
+        assert_eq!(file_information.get_size(), None);
+
+        let file_information =
+            FileInformation::new("", Box::new(|| "DATA".as_bytes().to_vec()), false, false);
+        assert_eq!(file_information.get_size(), Some(4));
+    }
+
     #[test]
     fn set_data_when_stored() {
         let (sender, receiver) = channel();
@@ -116,10 +210,13 @@ mod tests {
         let mut file_information = FileInformation {
             name: "".to_string(),
             data_fetcher: None,
+            size_fetcher: None,
             data: None,
             listed: false,
+            hidden: false,
             executable: false,
             writable: false,
+            scratch: false,
             on_data_change: Some(Box::new(move |received_data| {
                 assert_eq!(received_data, expected_data);
                 sender.send(()).unwrap();
@@ -141,15 +238,46 @@ mod tests {
     fn set_data_when_fetched() {
         let mut file_information = FileInformation {
             name: "".to_string(),
-            data_fetcher: Some(Box::new(|| "DATA".as_bytes().to_vec())),
+            data_fetcher: Some(Arc::new(|| "DATA".as_bytes().to_vec())),
+            size_fetcher: None,
             data: None,
             listed: false,
+            hidden: false,
             executable: false,
             writable: false,
+            scratch: false,
             on_data_change: None,
         };
         assert!(file_information
             .set_data("other".as_bytes().to_vec())
             .is_err());
     }
+
+    #[test]
+    fn set_data_when_fetched_and_writable() {
+        let stored = Arc::new(RwLock::new("DATA".to_string()));
+        let fetcher_stored = stored.clone();
+        let mut file_information = FileInformation::new_writable(
+            "",
+            Box::new(move || fetcher_stored.read().unwrap().as_bytes().to_vec()),
+            Box::new(move |data| {
+                *stored.write().unwrap() = data.to_uppercase();
+                Ok(())
+            }),
+        );
+        file_information
+            .set_data("other".as_bytes().to_vec())
+            .unwrap();
+        assert_eq!(file_information.get_data(), "OTHER".as_bytes().to_vec());
+    }
+
+    #[test]
+    fn set_data_when_scratch() {
+        let mut file_information = FileInformation::new_scratch(".config.yml.swp");
+        assert!(file_information.writable);
+        file_information
+            .set_data("anything".as_bytes().to_vec())
+            .unwrap();
+        assert_eq!(file_information.get_data(), "anything".as_bytes().to_vec());
+    }
 }
@@ -0,0 +1,83 @@
+use fuse::FileType;
+use std::collections::HashMap;
+
+pub struct DirectoryEntry {
+    pub inode_number: u64,
+    pub kind: FileType,
+    pub name: String,
+}
+
+impl DirectoryEntry {
+    pub fn new(inode_number: u64, kind: FileType, name: &str) -> Self {
+        DirectoryEntry {
+            inode_number,
+            kind,
+            name: name.to_string(),
+        }
+    }
+}
+
+// Entries of directories, held for open directory handles. Directories are listed from a snapshot
+// of their entries taken when opened, so that listings resume from an offset without the entries
+// being created again, and offsets remain consistent if the directory changes whilst open. Note:
+// the dynamic children of directories are not held, as they can be generated from any offset.
+#[derive(Default)]
+pub struct DirectoryListings {
+    listings: HashMap<u64, Vec<DirectoryEntry>>,
+    next_directory_handle: u64,
+}
+
+impl DirectoryListings {
+    pub fn new() -> Self {
+        DirectoryListings {
+            next_directory_handle: 1,
+            ..Default::default()
+        }
+    }
+
+    // Holds the given entries of a directory, returning the listing's directory handle
+    pub fn open(&mut self, entries: Vec<DirectoryEntry>) -> u64 {
+        let directory_handle = self.next_directory_handle;
+        self.next_directory_handle += 1;
+        self.listings.insert(directory_handle, entries);
+        directory_handle
+    }
+
+    pub fn get(&self, directory_handle: u64) -> Option<&[DirectoryEntry]> {
+        self.listings.get(&directory_handle).map(Vec::as_slice)
+    }
+
+    pub fn remove(&mut self, directory_handle: u64) -> Option<Vec<DirectoryEntry>> {
+        self.listings.remove(&directory_handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get() {
+        let mut directory_listings = DirectoryListings::new();
+        let directory_handle = directory_listings.open(vec![
+            DirectoryEntry::new(2, FileType::Directory, "a"),
+            DirectoryEntry::new(3, FileType::RegularFile, "b"),
+        ]);
+        let names: Vec<&str> = directory_listings
+            .get(directory_handle)
+            .unwrap()
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn remove() {
+        let mut directory_listings = DirectoryListings::new();
+        let directory_handle = directory_listings.open(vec![]);
+        assert!(directory_listings.remove(directory_handle).is_some());
+        assert!(directory_listings.get(directory_handle).is_none());
+        assert_ne!(directory_listings.open(vec![]), directory_handle);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::video_processing::VideoProperties;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VideoMetadata {
+    pub source: String,
+    pub number_of_frames: u64,
+    pub frames_per_second: f64,
+    pub width: u64,
+    pub height: u64,
+    pub fourcc: String,
+    pub duration_seconds: f64,
+}
+
+impl VideoMetadata {
+    pub fn new(source: &str, video_properties: &VideoProperties) -> Self {
+        VideoMetadata {
+            source: source.to_string(),
+            number_of_frames: video_properties.number_of_frames,
+            frames_per_second: video_properties.frames_per_second,
+            width: video_properties.width,
+            height: video_properties.height,
+            fourcc: fourcc_to_string(video_properties.fourcc),
+            duration_seconds: if video_properties.frames_per_second > 0.0 {
+                video_properties.number_of_frames as f64 / video_properties.frames_per_second
+            } else {
+                0.0
+            },
+        }
+    }
+
+    pub fn to_yaml(&self) -> Result<String, String> {
+        match serde_yaml::to_string(self) {
+            Ok(x) => Ok(x),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        match serde_json::to_string_pretty(self) {
+            Ok(x) => Ok(x + "\n"),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+// FourCC codes are four (little-endian) characters packed into an integer, e.g. "avc1"
+fn fourcc_to_string(fourcc: i32) -> String {
+    fourcc
+        .to_le_bytes()
+        .iter()
+        .map(|x| *x as char)
+        .filter(|x| x.is_ascii_graphic() || *x == ' ')
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIDEO_PROPERTIES: VideoProperties = VideoProperties {
+        number_of_frames: 150,
+        frames_per_second: 30.0,
+        width: 1920,
+        height: 1080,
+        fourcc: 0x31637661,
+    };
+
+    #[test]
+    fn new() {
+        let metadata = VideoMetadata::new("/videos/sample.mp4", &VIDEO_PROPERTIES);
+        assert_eq!(metadata.source, "/videos/sample.mp4");
+        assert_eq!(metadata.fourcc, "avc1");
+        assert_eq!(metadata.duration_seconds, 5.0);
+    }
+
+    #[test]
+    fn new_when_no_frames_per_second() {
+        let video_properties = VideoProperties {
+            frames_per_second: 0.0,
+            fourcc: 0,
+            ..VIDEO_PROPERTIES
+        };
+        let metadata = VideoMetadata::new("", &video_properties);
+        assert_eq!(metadata.fourcc, "");
+        assert_eq!(metadata.duration_seconds, 0.0);
+    }
+
+    #[test]
+    fn serialisation() {
+        let metadata = VideoMetadata::new("sample.mp4", &VIDEO_PROPERTIES);
+        let from_yaml: VideoMetadata = serde_yaml::from_str(&metadata.to_yaml().unwrap()).unwrap();
+        assert_eq!(from_yaml, metadata);
+        let from_json: VideoMetadata = serde_json::from_str(&metadata.to_json().unwrap()).unwrap();
+        assert_eq!(from_json, metadata);
+    }
+}
@@ -1,4 +1,10 @@
+pub mod buffers;
 pub mod config;
 pub mod file;
+pub mod listings;
 pub mod manifest;
+pub mod metadata;
 pub mod nodes;
+pub mod state;
+pub mod timestamp;
+pub mod views;
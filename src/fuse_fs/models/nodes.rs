@@ -2,10 +2,14 @@ use crate::fuse_fs::models::file::FileInformation;
 use fuse::{FileAttr, FileType};
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use std::time::SystemTime;
 use users::{get_current_gid, get_current_uid};
 
 pub const ROOT_INODE_NUMBER: u64 = 1;
+// Inode numbers reported in directory listings for dynamic children that have not yet been created
+// have this bit set, so they do not clash with the inode numbers of nodes
+const UNALLOCATED_INODE_NUMBER_FLAG: u64 = 1 << 63;
 
 pub fn create_directory_attributes(inode_number: u64) -> FileAttr {
     return FileAttr {
@@ -63,12 +67,63 @@ pub enum FuseNode<'a> {
     File(&'a FileFuseNode),
 }
 
+impl FuseNode<'_> {
+    pub fn get_inode_number(&self) -> u64 {
+        match self {
+            FuseNode::Directory(x) => x.get_inode_number(),
+            FuseNode::File(x) => x.get_inode_number(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            FuseNode::Directory(x) => x.name.as_str(),
+            FuseNode::File(x) => x.information.name.as_str(),
+        }
+    }
+}
+
+// Gets a distinct inode number to list a dynamic child that has not yet been created with, from the
+// directory's inode number and the child's index in the directory. Note: not 0, as entries with an
+// inode number of 0 are skipped by some libc implementations.
+pub fn get_unallocated_inode_number(directory_inode_number: u64, index: u64) -> u64 {
+    UNALLOCATED_INODE_NUMBER_FLAG | (directory_inode_number << 32) | (index & 0xFFFF_FFFF)
+}
+
+// Directory children that are listed by name but only created when looked up. The name generator
+// gets the name of the child at the given index, so that children can be listed from any index
+// without getting the names of those before it. The child generator creates the named child in the
+// given directory, returning its inode number (or `None` if there cannot be a child with the name).
+pub struct DynamicChildren {
+    number_of_children: u64,
+    name_generator: Box<dyn Fn(u64) -> String>,
+    child_generator: Box<dyn Fn(&mut FuseNodeStore, &str, u64) -> Option<u64>>,
+}
+
+impl DynamicChildren {
+    pub fn new(
+        number_of_children: u64,
+        name_generator: Box<dyn Fn(u64) -> String>,
+        child_generator: Box<dyn Fn(&mut FuseNodeStore, &str, u64) -> Option<u64>>,
+    ) -> Self {
+        DynamicChildren {
+            number_of_children,
+            name_generator,
+            child_generator,
+        }
+    }
+}
+
 pub struct DirectoryFuseNode {
     pub attributes: FileAttr,
     pub name: String,
     file_information_generator: Option<Box<dyn Fn(u64) -> Vec<FileInformation>>>,
     children_to_generate_from_file_information: bool,
     children_inode_numbers: Vec<u64>,
+    // Inode numbers of the children, keyed by name, so that children can be looked up without
+    // walking the directory
+    children_inode_numbers_by_name: HashMap<String, u64>,
+    dynamic_children: Option<Arc<DynamicChildren>>,
 }
 
 impl DirectoryFuseNode {
@@ -82,13 +137,30 @@ impl DirectoryFuseNode {
             name: name.to_string(),
             file_information_generator: Some(children_generator),
             children_inode_numbers: Default::default(),
+            children_inode_numbers_by_name: Default::default(),
             children_to_generate_from_file_information: true,
+            dynamic_children: None,
         }
     }
 
     pub fn get_inode_number(&self) -> u64 {
         self.attributes.ino
     }
+
+    fn add_child(&mut self, name: &str, inode_number: u64) {
+        self.children_inode_numbers.push(inode_number);
+        // Note: if names clash, the first child with the name is the one that is looked up
+        self.children_inode_numbers_by_name
+            .entry(name.to_string())
+            .or_insert(inode_number);
+    }
+
+    fn remove_child(&mut self, name: &str, inode_number: u64) {
+        self.children_inode_numbers.retain(|x| *x != inode_number);
+        if self.children_inode_numbers_by_name.get(name) == Some(&inode_number) {
+            self.children_inode_numbers_by_name.remove(name);
+        }
+    }
 }
 
 pub struct FileFuseNode {
@@ -107,18 +179,34 @@ impl FileFuseNode {
     }
 
     pub fn get_attributes(&self) -> FileAttr {
-        create_file_attributes(
+        self.get_attributes_and_is_size_known().0
+    }
+
+    // Gets the attributes of the file, along with whether its size is known. Note: the size of files
+    // is reported as 0 if it is not known without fetching their data.
+    pub fn get_attributes_and_is_size_known(&self) -> (FileAttr, bool) {
+        let size = self.information.get_size();
+        let attributes = create_file_attributes(
             self.get_inode_number(),
-            self.get_data().len() as u64,
+            size.unwrap_or(0),
             self.information.executable,
             self.information.writable,
-        )
+        );
+        (attributes, size.is_some())
     }
 }
 
+// A directory created from dynamic children, which is removed (with its contents) once FUSE has
+// forgotten about it
+struct EvictableDirectory {
+    parent_directory_inode_number: u64,
+    lookup_count: u64,
+}
+
 pub struct FuseNodeStore<'a> {
     file_nodes: HashMap<u64, Box<FileFuseNode>>,
     directory_nodes: HashMap<u64, Box<DirectoryFuseNode>>,
+    evictable_directories: HashMap<u64, EvictableDirectory>,
     current_inode_number: u64,
     phantom: PhantomData<&'a ()>,
 }
@@ -128,6 +216,7 @@ impl<'a> FuseNodeStore<'a> {
         let mut fuse_node_store = FuseNodeStore {
             file_nodes: Default::default(),
             directory_nodes: Default::default(),
+            evictable_directories: Default::default(),
             current_inode_number: ROOT_INODE_NUMBER,
             phantom: Default::default(),
         };
@@ -137,7 +226,9 @@ impl<'a> FuseNodeStore<'a> {
                 name: ".".to_string(),
                 file_information_generator: None,
                 children_inode_numbers: Default::default(),
+                children_inode_numbers_by_name: Default::default(),
                 children_to_generate_from_file_information: false,
+                dynamic_children: None,
             },
             ROOT_INODE_NUMBER,
         );
@@ -157,6 +248,7 @@ impl<'a> FuseNodeStore<'a> {
         parent_directory_inode_number: u64,
     ) {
         let inode_number = directory.get_inode_number();
+        let name = directory.name.clone();
         self.directory_nodes
             .insert(inode_number, Box::new(directory));
 
@@ -167,7 +259,7 @@ impl<'a> FuseNodeStore<'a> {
                 "Parent directory does not exist: {}",
                 parent_directory_inode_number
             ));
-        parent_directory.children_inode_numbers.push(inode_number);
+        parent_directory.add_child(&name, inode_number);
     }
 
     pub fn create_and_insert_directory(
@@ -181,7 +273,29 @@ impl<'a> FuseNodeStore<'a> {
             name: name.to_string(),
             file_information_generator: None,
             children_inode_numbers: Default::default(),
+            children_inode_numbers_by_name: Default::default(),
+            children_to_generate_from_file_information: false,
+            dynamic_children: None,
+        };
+        self.insert_directory(node, parent_directory_inode_number);
+        return inode_number;
+    }
+
+    pub fn create_and_insert_dynamic_directory(
+        &mut self,
+        name: &str,
+        parent_directory_inode_number: u64,
+        dynamic_children: DynamicChildren,
+    ) -> u64 {
+        let inode_number = self.create_inode_number();
+        let node = DirectoryFuseNode {
+            attributes: create_directory_attributes(inode_number),
+            name: name.to_string(),
+            file_information_generator: None,
+            children_inode_numbers: Default::default(),
+            children_inode_numbers_by_name: Default::default(),
             children_to_generate_from_file_information: false,
+            dynamic_children: Some(Arc::new(dynamic_children)),
         };
         self.insert_directory(node, parent_directory_inode_number);
         return inode_number;
@@ -193,6 +307,7 @@ impl<'a> FuseNodeStore<'a> {
         directory_inode_number: u64,
     ) -> u64 {
         let inode_number = self.create_inode_number();
+        let name = file_information.name.clone();
         let file_node = FileFuseNode {
             information: file_information,
             directory_inode_number,
@@ -206,11 +321,56 @@ impl<'a> FuseNodeStore<'a> {
                 "Could not get directory: {}",
                 directory_inode_number
             ))
-            .children_inode_numbers
-            .push(inode_number);
+            .add_child(&name, inode_number);
         return inode_number;
     }
 
+    pub fn remove_directory(&mut self, inode_number: u64, parent_directory_inode_number: u64) {
+        let directory = match self.directory_nodes.remove(&inode_number) {
+            Some(x) => x,
+            None => return,
+        };
+        self.evictable_directories.remove(&inode_number);
+        for child_inode_number in directory.children_inode_numbers {
+            if self.file_nodes.remove(&child_inode_number).is_none() {
+                self.remove_directory(child_inode_number, inode_number);
+            }
+        }
+
+        if let Some(parent_directory) = self.directory_nodes.get_mut(&parent_directory_inode_number)
+        {
+            parent_directory.remove_child(&directory.name, inode_number);
+        }
+    }
+
+    pub fn remove_file(&mut self, inode_number: u64) {
+        let file_node = match self.file_nodes.remove(&inode_number) {
+            Some(x) => x,
+            None => return,
+        };
+        if let Some(directory) = self
+            .directory_nodes
+            .get_mut(&file_node.directory_inode_number)
+        {
+            directory.remove_child(&file_node.information.name, inode_number);
+        }
+    }
+
+    pub fn rename_file(&mut self, inode_number: u64, name: &str) {
+        let file_node = match self.file_nodes.get_mut(&inode_number) {
+            Some(x) => x,
+            None => return,
+        };
+        let previous_name = std::mem::replace(&mut file_node.information.name, name.to_string());
+        if let Some(directory) = self
+            .directory_nodes
+            .get_mut(&file_node.directory_inode_number)
+        {
+            directory.remove_child(&previous_name, inode_number);
+            directory.add_child(name, inode_number);
+        }
+    }
+
     pub fn get_file_node(&self, inode_number: u64) -> Option<&FileFuseNode> {
         match self.file_nodes.get(&inode_number) {
             Some(boxed_node) => Some(boxed_node.as_ref()),
@@ -242,24 +402,124 @@ impl<'a> FuseNodeStore<'a> {
         }
     }
 
+    // Looks up a child of a directory, creating it from the directory's dynamic children if it has
+    // not been created. Note: children created from dynamic children are evicted once forgotten, so
+    // this should only be used to look up children for FUSE (see `record_lookup`).
     pub fn lookup_node(&mut self, name: &str, directory_inode_number: u64) -> Option<FuseNode> {
-        // TODO: specialised data structure to optimise
-        for child_node in self.get_nodes_in_directory(directory_inode_number) {
-            let node_name = match child_node {
-                FuseNode::Directory(x) => x.name.as_str(),
-                FuseNode::File(x) => x.information.name.as_str(),
-            };
-            if node_name == name {
-                return Some(child_node);
-            }
+        if let Some(inode_number) = self
+            .find_node(name, directory_inode_number)
+            .map(|x| x.get_inode_number())
+        {
+            return self.get_node(inode_number);
+        }
+
+        let dynamic_children = self
+            .get_directory_node(directory_inode_number)?
+            .dynamic_children
+            .clone()?;
+        let inode_number = (dynamic_children.child_generator)(self, name, directory_inode_number)?;
+        self.evictable_directories.insert(
+            inode_number,
+            EvictableDirectory {
+                parent_directory_inode_number: directory_inode_number,
+                lookup_count: 0,
+            },
+        );
+        return self.get_node(inode_number);
+    }
+
+    // Finds a child of a directory that has been created, without creating children from the
+    // directory's dynamic children
+    pub fn find_node(&mut self, name: &str, directory_inode_number: u64) -> Option<FuseNode> {
+        self.get_directory_node(directory_inode_number)?;
+        self.generate_children(directory_inode_number);
+        let inode_number = *self
+            .get_directory_node(directory_inode_number)?
+            .children_inode_numbers_by_name
+            .get(name)?;
+        self.get_node(inode_number)
+    }
+
+    // Records that a node has been looked up by FUSE, which will later forget it
+    pub fn record_lookup(&mut self, inode_number: u64) {
+        if let Some(evictable_directory) = self.evictable_directories.get_mut(&inode_number) {
+            evictable_directory.lookup_count += 1;
         }
-        return None;
+    }
+
+    // Evicts directories created from dynamic children once FUSE has forgotten all their lookups
+    pub fn forget(&mut self, inode_number: u64, number_of_lookups: u64) {
+        let evictable_directory = match self.evictable_directories.get_mut(&inode_number) {
+            Some(x) => x,
+            None => return,
+        };
+        evictable_directory.lookup_count = evictable_directory
+            .lookup_count
+            .saturating_sub(number_of_lookups);
+        if evictable_directory.lookup_count == 0 {
+            let parent_directory_inode_number = evictable_directory.parent_directory_inode_number;
+            self.evictable_directories.remove(&inode_number);
+            self.remove_directory(inode_number, parent_directory_inode_number);
+        }
+    }
+
+    // Gets the number of children listed in a directory with dynamic children
+    pub fn get_number_of_dynamic_children(&self, directory_inode_number: u64) -> Option<u64> {
+        Some(
+            self.get_directory_node(directory_inode_number)?
+                .dynamic_children
+                .as_ref()?
+                .number_of_children,
+        )
+    }
+
+    // Gets the child listed at the given index of a directory with dynamic children, as its inode
+    // number (see `get_unallocated_inode_number` if the child has not been created) and name
+    pub fn get_dynamic_child(
+        &self,
+        directory_inode_number: u64,
+        index: u64,
+    ) -> Option<(u64, String)> {
+        let directory = self.get_directory_node(directory_inode_number)?;
+        let dynamic_children = directory.dynamic_children.as_ref()?;
+        if index >= dynamic_children.number_of_children {
+            return None;
+        }
+        let name = (dynamic_children.name_generator)(index);
+        let inode_number = match directory.children_inode_numbers_by_name.get(&name) {
+            Some(x) => *x,
+            None => get_unallocated_inode_number(directory_inode_number, index),
+        };
+        Some((inode_number, name))
     }
 
     pub fn get_nodes_in_directory(&mut self, directory_inode_number: u64) -> Vec<FuseNode> {
         let non_existent_director_error =
             &format!("Non-existent directory: {}", directory_inode_number);
 
+        self.generate_children(directory_inode_number);
+
+        let directory = self
+            .directory_nodes
+            .get(&directory_inode_number)
+            .expect(non_existent_director_error);
+
+        let mut children = vec![];
+        for child_inode_number in &directory.children_inode_numbers {
+            let child_node = self
+                .get_node(*child_inode_number)
+                .expect(non_existent_director_error);
+            children.push(child_node);
+        }
+
+        return children;
+    }
+
+    // Creates the files of a directory from its file information generator, if not already created
+    fn generate_children(&mut self, directory_inode_number: u64) {
+        let non_existent_director_error =
+            &format!("Non-existent directory: {}", directory_inode_number);
+
         let directory = self
             .directory_nodes
             .get(&directory_inode_number)
@@ -279,21 +539,6 @@ impl<'a> FuseNodeStore<'a> {
             .get_mut(&directory_inode_number)
             .expect(non_existent_director_error)
             .children_to_generate_from_file_information = false;
-
-        let directory = self
-            .directory_nodes
-            .get(&directory_inode_number)
-            .expect(non_existent_director_error);
-
-        let mut children = vec![];
-        for child_inode_number in &directory.children_inode_numbers {
-            let child_node = self
-                .get_node(*child_inode_number)
-                .expect(non_existent_director_error);
-            children.push(child_node);
-        }
-
-        return children;
     }
 
     pub fn create_inode_number(&mut self) -> u64 {
@@ -305,6 +550,7 @@ impl<'a> FuseNodeStore<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_case::test_case;
 
     lazy_static! {
         static ref FILE_ATTR_1: FileAttr = FileAttr {
@@ -425,6 +671,219 @@ mod tests {
         );
     }
 
+    #[test]
+    fn node_store_remove_directory() {
+        let mut node_store = FuseNodeStore::new();
+        let root_inode_number = node_store.get_root_directory().get_inode_number();
+        let directory_inode_number =
+            node_store.create_and_insert_directory("test123", root_inode_number);
+        let sub_directory_inode_number =
+            node_store.create_and_insert_directory("test456", directory_inode_number);
+        let file_inode_number = node_store.create_and_insert_file(
+            FileInformation::new("", Box::new(|| vec![]), false, false),
+            sub_directory_inode_number,
+        );
+
+        node_store.remove_directory(directory_inode_number, root_inode_number);
+
+        assert!(node_store.get_node(directory_inode_number).is_none());
+        assert!(node_store.get_node(sub_directory_inode_number).is_none());
+        assert!(node_store.get_node(file_inode_number).is_none());
+        assert!(node_store
+            .lookup_node("test123", root_inode_number)
+            .is_none());
+    }
+
+    #[test]
+    fn node_store_remove_file() {
+        let mut node_store = FuseNodeStore::new();
+        let root_inode_number = node_store.get_root_directory().get_inode_number();
+        let inode_number = node_store
+            .create_and_insert_file(FileInformation::new_scratch("test123"), root_inode_number);
+
+        node_store.remove_file(inode_number);
+
+        assert!(node_store.get_node(inode_number).is_none());
+        assert!(node_store
+            .lookup_node("test123", root_inode_number)
+            .is_none());
+    }
+
+    #[test]
+    fn node_store_rename_file() {
+        let mut node_store = FuseNodeStore::new();
+        let root_inode_number = node_store.get_root_directory().get_inode_number();
+        let inode_number = node_store
+            .create_and_insert_file(FileInformation::new_scratch("test123"), root_inode_number);
+
+        node_store.rename_file(inode_number, "test456");
+
+        assert!(node_store
+            .lookup_node("test123", root_inode_number)
+            .is_none());
+        assert_eq!(
+            node_store
+                .lookup_node("test456", root_inode_number)
+                .unwrap()
+                .get_inode_number(),
+            inode_number
+        );
+    }
+
+    #[test]
+    fn node_store_lookup_generated_file() {
+        let mut node_store = FuseNodeStore::new();
+        let root_inode_number = node_store.get_root_directory().get_inode_number();
+        let directory = DirectoryFuseNode::new(
+            "test123",
+            create_directory_attributes(node_store.create_inode_number()),
+            Box::new(|_| vec![FileInformation::new_scratch("test456")]),
+        );
+        let directory_inode_number = directory.get_inode_number();
+        node_store.insert_directory(directory, root_inode_number);
+
+        assert_eq!(
+            node_store
+                .lookup_node("test456", directory_inode_number)
+                .unwrap()
+                .get_name(),
+            "test456"
+        );
+    }
+
+    #[test]
+    fn node_store_dynamic_directory() {
+        let mut node_store = FuseNodeStore::new();
+        let inode_number = node_store.create_and_insert_dynamic_directory(
+            "test123",
+            node_store.get_root_directory().get_inode_number(),
+            DynamicChildren::new(
+                2,
+                Box::new(|index| ["a", "b"][index as usize].to_string()),
+                Box::new(|node_store, name, directory_inode_number| {
+                    if name == "c" {
+                        return None;
+                    }
+                    Some(node_store.create_and_insert_directory(name, directory_inode_number))
+                }),
+            ),
+        );
+
+        assert_eq!(
+            node_store.get_number_of_dynamic_children(inode_number),
+            Some(2)
+        );
+        assert_eq!(
+            node_store.get_dynamic_child(inode_number, 0),
+            Some((
+                get_unallocated_inode_number(inode_number, 0),
+                "a".to_string()
+            ))
+        );
+        assert_eq!(
+            node_store.get_dynamic_child(inode_number, 1),
+            Some((
+                get_unallocated_inode_number(inode_number, 1),
+                "b".to_string()
+            ))
+        );
+        assert_eq!(node_store.get_dynamic_child(inode_number, 2), None);
+
+        let child_inode_number = node_store
+            .lookup_node("b", inode_number)
+            .unwrap()
+            .get_inode_number();
+        assert_eq!(
+            node_store
+                .lookup_node("b", inode_number)
+                .unwrap()
+                .get_inode_number(),
+            child_inode_number
+        );
+        assert!(node_store.lookup_node("c", inode_number).is_none());
+        assert_eq!(
+            node_store.get_dynamic_child(inode_number, 1),
+            Some((child_inode_number, "b".to_string()))
+        );
+    }
+
+    #[test]
+    fn node_store_find_node_in_dynamic_directory() {
+        let mut node_store = FuseNodeStore::new();
+        let inode_number = node_store.create_and_insert_dynamic_directory(
+            "test123",
+            node_store.get_root_directory().get_inode_number(),
+            DynamicChildren::new(
+                1,
+                Box::new(|_| "a".to_string()),
+                Box::new(|node_store, name, directory_inode_number| {
+                    Some(node_store.create_and_insert_directory(name, directory_inode_number))
+                }),
+            ),
+        );
+
+        assert!(node_store.find_node("a", inode_number).is_none());
+        assert!(node_store.get_nodes_in_directory(inode_number).is_empty());
+        let child_inode_number = node_store
+            .lookup_node("a", inode_number)
+            .unwrap()
+            .get_inode_number();
+        assert_eq!(
+            node_store
+                .find_node("a", inode_number)
+                .unwrap()
+                .get_inode_number(),
+            child_inode_number
+        );
+    }
+
+    #[test]
+    fn node_store_evicts_forgotten_dynamic_children() {
+        let mut node_store = FuseNodeStore::new();
+        let inode_number = node_store.create_and_insert_dynamic_directory(
+            "test123",
+            node_store.get_root_directory().get_inode_number(),
+            DynamicChildren::new(
+                0,
+                Box::new(|_| unreachable!()),
+                Box::new(|node_store, name, directory_inode_number| {
+                    Some(node_store.create_and_insert_directory(name, directory_inode_number))
+                }),
+            ),
+        );
+        let child_inode_number = node_store
+            .lookup_node("a", inode_number)
+            .unwrap()
+            .get_inode_number();
+        node_store.record_lookup(child_inode_number);
+        node_store.record_lookup(child_inode_number);
+
+        node_store.forget(child_inode_number, 1);
+        assert!(node_store.get_node(child_inode_number).is_some());
+        node_store.forget(child_inode_number, 1);
+        assert!(node_store.get_node(child_inode_number).is_none());
+        assert!(node_store.get_nodes_in_directory(inode_number).is_empty());
+
+        node_store.forget(inode_number, 1);
+        assert!(node_store.get_node(inode_number).is_some());
+    }
+
+    #[test_case(2, 0, 3, 1; "when different index")]
+    #[test_case(2, 0, 3, 0; "when different directory")]
+    fn unallocated_inode_numbers_are_distinct(
+        directory_inode_number: u64,
+        index: u64,
+        other_directory_inode_number: u64,
+        other_index: u64,
+    ) {
+        let inode_number = get_unallocated_inode_number(directory_inode_number, index);
+        assert_ne!(
+            inode_number,
+            get_unallocated_inode_number(other_directory_inode_number, other_index)
+        );
+        assert_ne!(inode_number, 0);
+    }
+
     #[test]
     fn node_store_get_file_node_not_exist() {
         let node_store = FuseNodeStore::new();
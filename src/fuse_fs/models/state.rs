@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, rename, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+// Configurations of the views of a video that are not the default, keyed by view name (and frame
+// number, where frames are numbered from 0). Configurations are held as they are written in each
+// view's `config.yml`, so that they can be parsed (and validated) by the view.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct VideoState {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub video: BTreeMap<String, serde_yaml::Value>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub frames: BTreeMap<u64, BTreeMap<String, serde_yaml::Value>>,
+}
+
+impl VideoState {
+    fn is_empty(&self) -> bool {
+        self.video.is_empty() && self.frames.is_empty()
+    }
+}
+
+// State of the file system that persists across mounts, keyed by video location
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct State {
+    pub videos: BTreeMap<String, VideoState>,
+}
+
+impl State {
+    pub fn from_yaml(yaml_string: &str) -> Result<Self, String> {
+        if yaml_string.trim().is_empty() {
+            return Ok(State::default());
+        }
+        serde_yaml::from_str(yaml_string).map_err(|e| e.to_string())
+    }
+
+    // Note: JSON is a subset of YAML but it is parsed as JSON, as frame numbers are (string) keys
+    pub fn from_json(json_string: &str) -> Result<Self, String> {
+        if json_string.trim().is_empty() {
+            return Ok(State::default());
+        }
+        serde_json::from_str(json_string).map_err(|e| e.to_string())
+    }
+
+    pub fn to_yaml(&self) -> Result<String, String> {
+        serde_yaml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    // Sets (or, if `None`, removes) the configuration of a view of a frame, or of the whole video
+    // if no frame number is given
+    pub fn set_configuration(
+        &mut self,
+        video_location: &str,
+        frame_number: Option<u64>,
+        view_name: &str,
+        configuration: Option<&str>,
+    ) -> Result<(), String> {
+        let configuration = match configuration {
+            Some(x) => Some(serde_yaml::from_str(x).map_err(|e| e.to_string())?),
+            None => None,
+        };
+        let video_state = self.videos.entry(video_location.to_string()).or_default();
+        let view_configurations = match frame_number {
+            Some(x) => video_state.frames.entry(x).or_default(),
+            None => &mut video_state.video,
+        };
+        match configuration {
+            Some(x) => view_configurations.insert(view_name.to_string(), x),
+            None => view_configurations.remove(view_name),
+        };
+
+        video_state.frames.retain(|_, x| !x.is_empty());
+        if video_state.is_empty() {
+            self.videos.remove(video_location);
+        }
+        Ok(())
+    }
+}
+
+// State that is loaded from, and saved to, a file
+#[derive(Debug)]
+pub struct StateFile {
+    location: String,
+    state: Mutex<State>,
+}
+
+impl StateFile {
+    // Loads the state from the given location, starting with no state if the file does not exist
+    pub fn load(location: &str) -> Result<Self, String> {
+        let state = if !Path::new(location).exists() {
+            State::default()
+        } else {
+            let data = read_to_string(location).map_err(|e| e.to_string())?;
+            if is_json_location(location) {
+                State::from_json(&data)?
+            } else {
+                State::from_yaml(&data)?
+            }
+        };
+        Ok(StateFile {
+            location: location.to_string(),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn get_video_state(&self, video_location: &str) -> VideoState {
+        self.state
+            .lock()
+            .unwrap()
+            .videos
+            .get(video_location)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Records a change to a configuration (see `State::set_configuration`) and saves the state
+    pub fn set_configuration(
+        &self,
+        video_location: &str,
+        frame_number: Option<u64>,
+        view_name: &str,
+        configuration: Option<&str>,
+    ) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        state.set_configuration(video_location, frame_number, view_name, configuration)?;
+        self.save(&state)
+    }
+
+    // Note: the state is written to a temporary file that is renamed over the state file, so that
+    //       the state file is never partially written. The temporary file is synced to disk before it
+    //       is renamed, so that a crash cannot leave an empty or truncated state file.
+    fn save(&self, state: &State) -> Result<(), String> {
+        let data = if is_json_location(&self.location) {
+            state.to_json()?
+        } else {
+            state.to_yaml()?
+        };
+        let temporary_location = format!("{}.tmp", self.location);
+        let mut file = File::create(&temporary_location).map_err(|e| e.to_string())?;
+        file.write_all(data.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(|e| e.to_string())?;
+        rename(&temporary_location, &self.location).map_err(|e| e.to_string())
+    }
+}
+
+// State files are YAML, unless they have a `.json` extension
+fn is_json_location(location: &str) -> bool {
+    location.to_lowercase().ends_with(".json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+    use test_case::test_case;
+
+    #[test]
+    fn set_configuration() {
+        let mut state = State::default();
+        state
+            .set_configuration("video.mp4", Some(3), "resized", Some("width: 64"))
+            .unwrap();
+        state
+            .set_configuration("video.mp4", None, "edges", Some("operator: sobel"))
+            .unwrap();
+        assert_eq!(
+            state.to_yaml().unwrap(),
+            "videos:\n  video.mp4:\n    video:\n      edges:\n        operator: sobel\n    \
+                frames:\n      3:\n        resized:\n          width: 64\n"
+        );
+    }
+
+    #[test]
+    fn set_configuration_when_removed() {
+        let mut state = State::default();
+        state
+            .set_configuration("video.mp4", Some(3), "resized", Some("width: 64"))
+            .unwrap();
+        state
+            .set_configuration("video.mp4", Some(3), "resized", None)
+            .unwrap();
+        assert_eq!(state, State::default());
+    }
+
+    #[test_case("state.yml"; "when yaml")]
+    #[test_case("state.json"; "when json")]
+    fn state_file_round_trip(file_name: &str) {
+        let location = temp_dir()
+            .join(format!(
+                "video-frame-fuse-{}-{}",
+                std::process::id(),
+                file_name
+            ))
+            .display()
+            .to_string();
+        let state_file = StateFile::load(&location).unwrap();
+        assert_eq!(
+            state_file.get_video_state("video.mp4"),
+            VideoState::default()
+        );
+        state_file
+            .set_configuration(
+                "video.mp4",
+                Some(1),
+                "black-and-white",
+                Some("threshold: 42"),
+            )
+            .unwrap();
+
+        let loaded_state_file = StateFile::load(&location).unwrap();
+        remove_file(&location).unwrap();
+        assert_eq!(
+            loaded_state_file.get_video_state("video.mp4").frames[&1]["black-and-white"],
+            serde_yaml::from_str::<serde_yaml::Value>("threshold: 42").unwrap()
+        );
+    }
+
+    #[test_case("videos: []"; "when videos is not a mapping")]
+    #[test_case("other: {}"; "when unknown field")]
+    fn state_when_invalid(string: &str) {
+        assert!(State::from_yaml(string).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+// Timestamps are formatted as `HH-MM-SS.mmm`, as colons are awkward in file names
+pub fn format_timestamp(milliseconds: u64) -> String {
+    let seconds = milliseconds / 1000;
+    format!(
+        "{:02}-{:02}-{:02}.{:03}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60,
+        milliseconds % 1000
+    )
+}
+
+// Parses `HH-MM-SS`, with optional fractional seconds (e.g. `00-01-23.5`), into milliseconds
+pub fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let (time, fraction) = match timestamp.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (timestamp, None),
+    };
+
+    let parts: Vec<&str> = time.split('-').collect();
+    if parts.len() != 3 || parts.iter().any(|x| !is_digits(x)) {
+        return None;
+    }
+    let hours: u64 = parts[0].parse().ok()?;
+    let minutes: u64 = parts[1].parse().ok()?;
+    let seconds: u64 = parts[2].parse().ok()?;
+    if minutes >= 60 || seconds >= 60 {
+        return None;
+    }
+
+    let milliseconds = match fraction {
+        None => 0,
+        Some(x) if x.len() <= 3 && is_digits(x) => format!("{:0<3}", x).parse().ok()?,
+        Some(_) => return None,
+    };
+
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + milliseconds)
+}
+
+pub fn get_frame_timestamp(frame_number: u64, frames_per_second: f64) -> u64 {
+    (frame_number as f64 * 1000.0 / frames_per_second).round() as u64
+}
+
+// Gets the frame being shown at the given time (or `None` if the time is outside of the video)
+pub fn get_nearest_frame_number(
+    milliseconds: u64,
+    frames_per_second: f64,
+    number_of_frames: u64,
+) -> Option<u64> {
+    if frames_per_second <= 0.0 || number_of_frames == 0 {
+        return None;
+    }
+    let frame_number = (milliseconds as f64 * frames_per_second / 1000.0).round() as u64;
+    if frame_number < number_of_frames {
+        Some(frame_number)
+    } else if milliseconds <= get_frame_timestamp(number_of_frames, frames_per_second) {
+        Some(number_of_frames - 1)
+    } else {
+        None
+    }
+}
+
+fn is_digits(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|x| x.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, "00-00-00.000"; "when zero")]
+    #[test_case(83_500, "00-01-23.500"; "when minutes")]
+    #[test_case(3_723_004, "01-02-03.004"; "when hours")]
+    fn timestamp_formatting(milliseconds: u64, expected: &str) {
+        assert_eq!(format_timestamp(milliseconds), expected);
+        assert_eq!(parse_timestamp(expected), Some(milliseconds));
+    }
+
+    #[test_case("00-01-23", Some(83_000); "when no fraction")]
+    #[test_case("00-01-23.5", Some(83_500); "when short fraction")]
+    #[test_case("100-00-00.000", Some(360_000_000); "when many hours")]
+    #[test_case("00-60-00.000", None; "when minutes out of range")]
+    #[test_case("00-01-23.5000", None; "when long fraction")]
+    #[test_case("00-01.500", None; "when missing part")]
+    #[test_case("frame-1", None; "when not timestamp")]
+    fn timestamp_parsing(timestamp: &str, expected: Option<u64>) {
+        assert_eq!(parse_timestamp(timestamp), expected);
+    }
+
+    #[test_case(0, Some(0); "when start")]
+    #[test_case(1_016, Some(30); "when rounding down")]
+    #[test_case(1_017, Some(31); "when rounding up")]
+    #[test_case(3_320, Some(99); "when in last frame")]
+    #[test_case(3_334, None; "when after end")]
+    fn nearest_frame_number(milliseconds: u64, expected: Option<u64>) {
+        assert_eq!(get_nearest_frame_number(milliseconds, 30.0, 100), expected);
+    }
+}
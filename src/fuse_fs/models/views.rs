@@ -0,0 +1,123 @@
+use crate::fuse_fs::models::config::{Configuration, PipelineConfiguration};
+use std::fs::read_to_string;
+
+// A view declared in a views file, which applies a pipeline of steps to frames
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineViewDefinition {
+    pub name: String,
+    // The view's configuration, until it is changed via the view's `config.yml`
+    pub configuration: PipelineConfiguration,
+}
+
+pub fn load_pipeline_view_definitions(
+    location: &str,
+    reserved_names: &[&str],
+) -> Result<Vec<PipelineViewDefinition>, String> {
+    let yaml_string = read_to_string(location).map_err(|e| e.to_string())?;
+    parse_pipeline_view_definitions(&yaml_string, reserved_names)
+}
+
+// Parses views, which are declared as a mapping of view name to the contents of the view's
+// `config.yml`, e.g. `outlines: {steps: [greyscale, {edges: {operator: sobel}}]}`. Note: views are
+// kept in the order they are declared, which is the order they are listed in.
+pub fn parse_pipeline_view_definitions(
+    yaml_string: &str,
+    reserved_names: &[&str],
+) -> Result<Vec<PipelineViewDefinition>, String> {
+    if yaml_string.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let views =
+        serde_yaml::from_str::<serde_yaml::Mapping>(yaml_string).map_err(|e| e.to_string())?;
+
+    let mut definitions = vec![];
+    for (name, configuration) in views {
+        let name = match name {
+            serde_yaml::Value::String(x) => x,
+            x => return Err(format!("View names must be strings: {:?}", x)),
+        };
+        validate_view_name(&name, reserved_names)?;
+        let configuration = serde_yaml::to_string(&configuration).map_err(|e| e.to_string())?;
+        definitions.push(PipelineViewDefinition {
+            configuration: PipelineConfiguration::from_yaml(&configuration)
+                .map_err(|e| format!("Invalid view \"{}\": {}", name, e))?,
+            name,
+        });
+    }
+    Ok(definitions)
+}
+
+fn validate_view_name(name: &str, reserved_names: &[&str]) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+        return Err(format!("Invalid view name: \"{}\"", name));
+    }
+    if reserved_names.contains(&name) {
+        return Err(format!(
+            "View name is already used by a built-in view: {}",
+            name
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse_fs::models::config::PipelineStep;
+    use indoc::indoc;
+    use test_case::test_case;
+
+    #[test]
+    fn parse_definitions() {
+        let yaml = indoc! {"
+            outlines:
+              steps:
+              - greyscale
+              - edges:
+                  operator: sobel
+            blurred:
+              steps:
+              - gaussian-blur: {kernel_size: 9}
+              encoding:
+                jpg: {quality: 50}
+        "};
+        let definitions = parse_pipeline_view_definitions(yaml, &["original"]).unwrap();
+        assert_eq!(
+            definitions
+                .iter()
+                .map(|x| x.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["outlines", "blurred"]
+        );
+        assert_eq!(
+            definitions[1]
+                .configuration
+                .encoding
+                .jpg
+                .as_ref()
+                .unwrap()
+                .quality,
+            50
+        );
+        assert_eq!(
+            definitions[0].configuration.steps[0],
+            PipelineStep::Greyscale
+        );
+    }
+
+    #[test]
+    fn parse_definitions_when_empty() {
+        assert_eq!(parse_pipeline_view_definitions("", &[]).unwrap(), vec![]);
+    }
+
+    #[test_case("original: {steps: [greyscale]}"; "when reserved name")]
+    #[test_case("a/b: {steps: [greyscale]}"; "when name is a path")]
+    #[test_case("..: {steps: [greyscale]}"; "when name is parent directory")]
+    #[test_case("outlines: {steps: [blur]}"; "when unknown step")]
+    #[test_case("outlines: {steps: [greyscale], colour: red}"; "when unknown field")]
+    #[test_case("- outlines"; "when not a mapping")]
+    #[test_case("1: {steps: [greyscale]}"; "when name is not a string")]
+    fn parse_definitions_when_invalid(yaml: &str) {
+        assert!(parse_pipeline_view_definitions(yaml, &["original"]).is_err());
+    }
+}
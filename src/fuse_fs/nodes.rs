@@ -1,132 +1,976 @@
 use crate::fuse_fs::models::config::{
-    BlackAndWhiteConfiguration, Configuration, ConfigurationHolder,
+    BasicConfiguration, BlackAndWhiteConfiguration, Configuration, ConfigurationHolder,
+    ConfigurationSource, ConfigurationStore, CroppedConfiguration, EdgesConfiguration,
+    PipelineConfiguration, ResizedConfiguration,
 };
-use crate::fuse_fs::models::file::FileInformation;
+use crate::fuse_fs::models::file::{DataChangeHandler, FileInformation};
 use crate::fuse_fs::models::manifest::DirectoryManifest;
+use crate::fuse_fs::models::metadata::VideoMetadata;
 use crate::fuse_fs::models::nodes::{
-    create_directory_attributes, DirectoryFuseNode, FuseNodeStore,
+    create_directory_attributes, DirectoryFuseNode, DynamicChildren, FuseNodeStore,
 };
+use crate::fuse_fs::models::state::StateFile;
+use crate::fuse_fs::models::timestamp::{
+    format_timestamp, get_frame_timestamp, get_nearest_frame_number, parse_timestamp,
+};
+use crate::fuse_fs::models::views::{load_pipeline_view_definitions, PipelineViewDefinition};
+use crate::video_processing::cache::{EncodedImageKey, FRAME_CACHE};
+use crate::video_processing::threshold::ComputedThreshold;
 use crate::video_processing::{
-    get_black_and_white_frame_image, get_frame_image, get_greyscale_frame_image,
-    get_number_of_frames, ImageType,
+    get_black_and_white_frame_image, get_black_and_white_threshold, get_cropped_frame_image,
+    get_edges_frame_image, get_frame_image, get_greyscale_frame_image, get_pipeline_frame_image,
+    get_resized_frame_image, get_video_properties, FrameOutput, ImageType, Region, VideoProperties,
 };
 use fuse::FileAttr;
-use log::{debug, info};
+use log::{debug, error, info, warn};
+use std::fs::canonicalize;
 use std::sync::{Arc, RwLock};
 use strum::IntoEnumIterator;
 
 lazy_static! {
-    static ref DEFAULT_VIEW_GENERATORS: Vec<fn(&str, u64, u64) -> DirectoryFuseNode> = vec![
-        create_original_view,
-        create_greyscale_view,
-        create_black_and_white_view,
+    // The views created for every frame, which is the only place they are declared (the generators,
+    // names and configurable views of the default views are all derived from it)
+    static ref DEFAULT_VIEWS: Vec<DefaultView> = vec![
+        DefaultView::new(
+            create_original_view,
+            ConfigurableView::new(
+                "original",
+                &parse_basic_configuration,
+                ConfigurationHolder::Basic(BasicConfiguration::default()),
+            ),
+        ),
+        DefaultView::new(
+            create_greyscale_view,
+            ConfigurableView::new(
+                "greyscale",
+                &parse_basic_configuration,
+                ConfigurationHolder::Basic(BasicConfiguration::default()),
+            ),
+        ),
+        DefaultView::new(
+            create_black_and_white_view,
+            ConfigurableView::new(
+                "black-and-white",
+                &parse_black_and_white_configuration,
+                ConfigurationHolder::BlackAndWhite(BlackAndWhiteConfiguration::default()),
+            ),
+        ),
+        DefaultView::new(
+            create_cropped_view,
+            ConfigurableView::new(
+                "cropped",
+                &parse_cropped_configuration,
+                ConfigurationHolder::Cropped(CroppedConfiguration::default()),
+            ),
+        ),
+        DefaultView::new(
+            create_resized_view,
+            ConfigurableView::new(
+                "resized",
+                &parse_resized_configuration,
+                ConfigurationHolder::Resized(ResizedConfiguration::default()),
+            ),
+        ),
+        DefaultView::new(
+            create_edges_view,
+            ConfigurableView::new(
+                "edges",
+                &parse_edges_configuration,
+                ConfigurationHolder::Edges(EdgesConfiguration::default()),
+            ),
+        ),
     ];
+    static ref DEFAULT_VIEW_GENERATORS: Vec<ViewGenerator> =
+        DEFAULT_VIEWS.iter().map(|x| x.generator).collect();
+    // Names of the default views, which views declared by users cannot use
+    pub static ref DEFAULT_VIEW_NAMES: Vec<&'static str> = DEFAULT_VIEWS
+        .iter()
+        .map(|x| x.configurable_view.name.as_str())
+        .collect();
+}
+
+pub type ViewGenerator = fn(&VideoContext, u64, u64) -> DirectoryFuseNode;
+// Produces the data of a view's file (for the given frame number and output), using the view's
+// configuration
+pub type ImageDataGenerator =
+    dyn Fn(&VideoContext, u64, FrameOutput, ConfigurationHolder) -> Vec<u8> + Send + Sync;
+// Parses (and validates) a view's configuration
+pub type ConfigurationParser =
+    dyn Fn(&str, &VideoContext) -> Result<ConfigurationHolder, String> + Send + Sync;
+// Produces the contents of a view's read-only `computed.yml` file, which holds values calculated
+// whilst producing the frame's images with the view's configuration
+pub type ComputedDataGenerator =
+    dyn Fn(&VideoContext, u64, ConfigurationHolder) -> Vec<u8> + Send + Sync;
+
+// The name of the directory, in `by-frame`, of the video-wide configurations of views
+pub const VIDEO_CONFIGURATION_DIRECTORY_NAME: &str = ".config";
+
+// A view that can be configured, for every frame or for the whole video
+#[derive(Clone)]
+pub struct ConfigurableView {
+    pub name: String,
+    pub parser: &'static ConfigurationParser,
+    pub default_configuration: ConfigurationHolder,
+}
+
+impl ConfigurableView {
+    pub fn new(
+        name: &str,
+        parser: &'static ConfigurationParser,
+        default_configuration: ConfigurationHolder,
+    ) -> Self {
+        ConfigurableView {
+            name: name.to_string(),
+            parser,
+            default_configuration,
+        }
+    }
+}
+
+// A view that is created for every frame, by the given generator
+pub struct DefaultView {
+    pub generator: ViewGenerator,
+    pub configurable_view: ConfigurableView,
+}
+
+impl DefaultView {
+    pub fn new(generator: ViewGenerator, configurable_view: ConfigurableView) -> Self {
+        DefaultView {
+            generator,
+            configurable_view,
+        }
+    }
+}
+
+// Gets how a default view is configured. Note: panics if the view is not in `DEFAULT_VIEWS`, so that
+// a view that is missing from it is noticed as soon as it is created.
+fn get_default_view(name: &str) -> &'static ConfigurableView {
+    &DEFAULT_VIEWS
+        .iter()
+        .find(|x| x.configurable_view.name == name)
+        .expect(&format!("Not a default view: {}", name))
+        .configurable_view
+}
+
+// Which of the image files of views are listed in directory listings. Image files that are not listed
+// can still be accessed, after which they are listed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ListingMode {
+    // Image files are only listed once they have been accessed
+    #[default]
+    Lazy,
+    All,
+    // Only image files of the given types are listed
+    Formats(Vec<ImageType>),
+}
+
+impl ListingMode {
+    // Parses a listing mode, i.e. `lazy`, `all` or `formats=<types>` (e.g. `formats=png,jpg`)
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "lazy" => return Ok(ListingMode::Lazy),
+            "all" => return Ok(ListingMode::All),
+            _ => {}
+        }
+        let image_types = value
+            .strip_prefix("formats=")
+            .ok_or(format!("Invalid listing mode: {}", value))?
+            .split(',')
+            .map(|name| {
+                ImageType::iter()
+                    .find(|x| x.to_string() == name)
+                    .ok_or(format!("Invalid image type: {}", name))
+            })
+            .collect::<Result<Vec<ImageType>, String>>()?;
+        Ok(ListingMode::Formats(image_types))
+    }
+
+    pub fn is_listed(&self, image_type: ImageType) -> bool {
+        match self {
+            ListingMode::Lazy => false,
+            ListingMode::All => true,
+            ListingMode::Formats(x) => x.contains(&image_type),
+        }
+    }
+
+    // Whether image files of the given type are in the manifest of the view, i.e. the files that
+    // `initialise.sh` accesses so that they are listed
+    pub fn is_in_manifest(&self, image_type: ImageType) -> bool {
+        match self {
+            ListingMode::Formats(x) => x.contains(&image_type),
+            _ => true,
+        }
+    }
 }
 
-pub fn create_default_video_nodes(video_location: &str) -> FuseNodeStore {
-    create_video_nodes(video_location, DEFAULT_VIEW_GENERATORS.to_vec())
+// Options applied to the nodes of every video
+#[derive(Clone, Debug, Default)]
+pub struct VideoNodesOptions {
+    // Frames are numbered from this number in the file system, whereas frame numbers used
+    // internally are always the 0-based index of the frame in the video
+    pub first_frame_number: u64,
+    // Views declared by the user, which are created for every frame alongside the default views
+    pub pipeline_views: Vec<PipelineViewDefinition>,
+    // Where configurations are loaded from and saved to, so that they persist across mounts
+    pub state_file: Option<Arc<StateFile>>,
+    pub listing_mode: ListingMode,
+}
+
+// State shared between all the nodes of a video. Note: configurations are not held by the nodes, as
+// frame nodes are evicted when no longer in use.
+#[derive(Clone)]
+pub struct VideoContext {
+    pub video_location: String,
+    // The canonical location of the video (if it can be resolved)
+    pub source: String,
+    pub video_properties: VideoProperties,
+    pub first_frame_number: u64,
+    pub pipeline_views: Arc<Vec<PipelineViewDefinition>>,
+    pub configurations: Arc<ConfigurationStore>,
+    pub state_file: Option<Arc<StateFile>>,
+    pub listing_mode: ListingMode,
+}
+
+impl VideoContext {
+    pub fn new(
+        video_location: &str,
+        video_properties: &VideoProperties,
+        options: &VideoNodesOptions,
+    ) -> Self {
+        let source = match canonicalize(video_location) {
+            Ok(x) => x.display().to_string(),
+            Err(_) => video_location.to_string(),
+        };
+        VideoContext {
+            video_location: video_location.to_string(),
+            source,
+            video_properties: *video_properties,
+            first_frame_number: options.first_frame_number,
+            pipeline_views: Arc::new(options.pipeline_views.clone()),
+            configurations: Arc::new(ConfigurationStore::new()),
+            state_file: options.state_file.clone(),
+            listing_mode: options.listing_mode.clone(),
+        }
+    }
+
+    pub fn get_frame_name(&self, frame_number: u64) -> String {
+        format!("frame-{}", frame_number + self.first_frame_number)
+    }
+
+    // Gets the frame number from a frame name, if it is a valid name
+    pub fn parse_frame_name(&self, name: &str) -> Option<u64> {
+        let frame_number = name
+            .strip_prefix("frame-")?
+            .parse::<u64>()
+            .ok()?
+            .checked_sub(self.first_frame_number)?;
+        if self.get_frame_name(frame_number) != name {
+            return None;
+        }
+        Some(frame_number)
+    }
+
+    // Removes a configuration that was recorded in the state file (if any), e.g. when a frame's
+    // configuration is cleared so that it is inherited again
+    pub fn clear_configuration(&self, frame_number: Option<u64>, view_name: &str) {
+        let state_file = match &self.state_file {
+            Some(x) => x,
+            None => return,
+        };
+        if let Err(e) = state_file.set_configuration(&self.source, frame_number, view_name, None) {
+            error!("Could not save configuration to state file: {}", e);
+        }
+    }
+
+    // Records a configuration that has been set for a view of a frame, or of the whole video if no
+    // frame number is given, in the state file (if any). Note: a configuration that is the same as
+    // the one it would otherwise inherit is not recorded (and any recorded one is removed), so that
+    // it continues to follow the configuration it inherits when that changes.
+    pub fn save_configuration(
+        &self,
+        frame_number: Option<u64>,
+        view_name: &str,
+        configuration: &ConfigurationHolder,
+        inherited_configuration: &ConfigurationHolder,
+    ) {
+        let state_file = match &self.state_file {
+            Some(x) => x,
+            None => return,
+        };
+        let configuration = configuration.to_yaml().unwrap();
+        let is_recorded = configuration != inherited_configuration.to_yaml().unwrap();
+        if let Err(e) = state_file.set_configuration(
+            &self.source,
+            frame_number,
+            view_name,
+            Some(configuration.as_str()).filter(|_| is_recorded),
+        ) {
+            error!("Could not save configuration to state file: {}", e);
+        }
+    }
+}
+
+pub fn load_pipeline_views(location: &str) -> Result<Vec<PipelineViewDefinition>, String> {
+    load_pipeline_view_definitions(location, &DEFAULT_VIEW_NAMES)
+}
+
+pub fn create_default_video_nodes(
+    video_location: &str,
+    options: &VideoNodesOptions,
+) -> Result<FuseNodeStore<'static>, String> {
+    create_video_nodes(video_location, options, DEFAULT_VIEW_GENERATORS.to_vec())
 }
 
 pub fn create_video_nodes(
     video_location: &str,
-    view_generators: Vec<fn(&str, u64, u64) -> DirectoryFuseNode>,
-) -> FuseNodeStore {
+    options: &VideoNodesOptions,
+    view_generators: Vec<ViewGenerator>,
+) -> Result<FuseNodeStore<'static>, String> {
+    let video_properties = get_video_properties(video_location)?;
     let mut node_store = FuseNodeStore::new();
     let root_directory_inode_number = node_store.get_root_directory().get_inode_number();
-    let by_frame_directory_inode_number =
-        node_store.create_and_insert_directory("by-frame", root_directory_inode_number);
-
-    let number_of_frames = get_number_of_frames(video_location);
-    for frame_number in 1..number_of_frames as u64 {
-        let frame_directory_inode_number = node_store.create_and_insert_directory(
-            &format!("frame-{}", frame_number),
-            by_frame_directory_inode_number,
-        );
+    insert_video_nodes(
+        &mut node_store,
+        video_location,
+        &video_properties,
+        options,
+        root_directory_inode_number,
+        view_generators,
+    );
+    Ok(node_store)
+}
 
-        for view_generator in &view_generators {
-            let view_directory = view_generator(
-                video_location,
-                frame_number,
-                node_store.create_inode_number(),
+pub fn insert_default_video_nodes(
+    node_store: &mut FuseNodeStore,
+    video_location: &str,
+    video_properties: &VideoProperties,
+    options: &VideoNodesOptions,
+    directory_inode_number: u64,
+) {
+    insert_video_nodes(
+        node_store,
+        video_location,
+        video_properties,
+        options,
+        directory_inode_number,
+        DEFAULT_VIEW_GENERATORS.to_vec(),
+    )
+}
+
+pub fn insert_video_nodes(
+    node_store: &mut FuseNodeStore,
+    video_location: &str,
+    video_properties: &VideoProperties,
+    options: &VideoNodesOptions,
+    directory_inode_number: u64,
+    view_generators: Vec<ViewGenerator>,
+) {
+    let video_context = VideoContext::new(video_location, video_properties, options);
+    insert_metadata_files(
+        node_store,
+        &VideoMetadata::new(&video_context.source, video_properties),
+        directory_inode_number,
+    );
+    load_configurations(&video_context);
+    let number_of_frames = video_properties.number_of_frames;
+
+    let listing_video_context = video_context.clone();
+    let movable_video_context = video_context.clone();
+    let movable_view_generators = view_generators.clone();
+    node_store.create_and_insert_dynamic_directory(
+        "by-frame",
+        directory_inode_number,
+        DynamicChildren::new(
+            number_of_frames + 1,
+            Box::new(move |index| match index {
+                0 => VIDEO_CONFIGURATION_DIRECTORY_NAME.to_string(),
+                _ => listing_video_context.get_frame_name(index - 1),
+            }),
+            Box::new(move |node_store, name, by_frame_directory_inode_number| {
+                if name == VIDEO_CONFIGURATION_DIRECTORY_NAME {
+                    return Some(insert_video_configuration_nodes(
+                        node_store,
+                        &movable_video_context,
+                        by_frame_directory_inode_number,
+                    ));
+                }
+                let frame_number = movable_video_context.parse_frame_name(name)?;
+                if frame_number >= number_of_frames {
+                    return None;
+                }
+                Some(insert_frame_nodes(
+                    node_store,
+                    &movable_video_context,
+                    frame_number,
+                    name,
+                    by_frame_directory_inode_number,
+                    &movable_view_generators,
+                ))
+            }),
+        ),
+    );
+
+    let frames_per_second = video_properties.frames_per_second;
+    node_store.create_and_insert_dynamic_directory(
+        "by-time",
+        directory_inode_number,
+        DynamicChildren::new(
+            if frames_per_second > 0.0 {
+                number_of_frames
+            } else {
+                0
+            },
+            Box::new(move |frame_number| {
+                format_timestamp(get_frame_timestamp(frame_number, frames_per_second))
+            }),
+            Box::new(move |node_store, name, by_time_directory_inode_number| {
+                let frame_number = get_nearest_frame_number(
+                    parse_timestamp(name)?,
+                    frames_per_second,
+                    number_of_frames,
+                )?;
+                debug!("Resolved time {} to frame {}", name, frame_number);
+                Some(insert_frame_nodes(
+                    node_store,
+                    &video_context,
+                    frame_number,
+                    name,
+                    by_time_directory_inode_number,
+                    &view_generators,
+                ))
+            }),
+        ),
+    );
+}
+
+// Loads the configurations of the video that were saved in the state file (if any)
+fn load_configurations(video_context: &VideoContext) {
+    let state_file = match &video_context.state_file {
+        Some(x) => x,
+        None => return,
+    };
+    let video_state = state_file.get_video_state(&video_context.source);
+    let configurable_views = get_configurable_views(video_context);
+    let parse_configuration = |view_name: &str, configuration: &serde_yaml::Value| {
+        let configurable_view = configurable_views
+            .iter()
+            .find(|x| x.name == view_name)
+            .ok_or_else(|| format!("Unknown view: {}", view_name))?;
+        let configuration = serde_yaml::to_string(configuration).map_err(|e| e.to_string())?;
+        (configurable_view.parser)(&configuration, video_context)
+    };
+
+    for (view_name, configuration) in &video_state.video {
+        match parse_configuration(view_name, configuration) {
+            Ok(x) => video_context.configurations.set_video(view_name, x),
+            Err(e) => warn!(
+                "Ignoring saved video-wide configuration of {}: {}",
+                view_name, e
+            ),
+        }
+    }
+    for (frame_number, view_configurations) in &video_state.frames {
+        if *frame_number >= video_context.video_properties.number_of_frames {
+            warn!(
+                "Ignoring saved configurations of non-existent frame: {}",
+                frame_number
             );
-            node_store.insert_directory(view_directory, frame_directory_inode_number);
+            continue;
+        }
+        for (view_name, configuration) in view_configurations {
+            match parse_configuration(view_name, configuration) {
+                Ok(x) => video_context
+                    .configurations
+                    .set(*frame_number, view_name, x),
+                Err(e) => warn!(
+                    "Ignoring saved configuration of {} for frame {}: {}",
+                    view_name, frame_number, e
+                ),
+            }
         }
     }
+    info!(
+        "Loaded saved configurations of video: {}",
+        video_context.source
+    );
+}
 
-    node_store
+fn insert_metadata_files(
+    node_store: &mut FuseNodeStore,
+    video_metadata: &VideoMetadata,
+    directory_inode_number: u64,
+) {
+    node_store.create_and_insert_file(
+        FileInformation::new_with_data(
+            "video.yml",
+            video_metadata.to_yaml().unwrap().into_bytes(),
+            true,
+            false,
+            false,
+            None,
+        ),
+        directory_inode_number,
+    );
+    node_store.create_and_insert_file(
+        FileInformation::new_with_data(
+            "video.json",
+            video_metadata.to_json().unwrap().into_bytes(),
+            true,
+            false,
+            false,
+            None,
+        ),
+        directory_inode_number,
+    );
+}
+
+fn insert_frame_nodes(
+    node_store: &mut FuseNodeStore,
+    video_context: &VideoContext,
+    frame_number: u64,
+    name: &str,
+    directory_inode_number: u64,
+    view_generators: &[ViewGenerator],
+) -> u64 {
+    let frame_directory_inode_number =
+        node_store.create_and_insert_directory(name, directory_inode_number);
+
+    for view_generator in view_generators {
+        let view_directory = view_generator(
+            video_context,
+            frame_number,
+            node_store.create_inode_number(),
+        );
+        node_store.insert_directory(view_directory, frame_directory_inode_number);
+    }
+    for pipeline_view in video_context.pipeline_views.iter() {
+        let view_directory = create_pipeline_view(
+            video_context,
+            frame_number,
+            node_store.create_inode_number(),
+            pipeline_view,
+        );
+        node_store.insert_directory(view_directory, frame_directory_inode_number);
+    }
+
+    frame_directory_inode_number
 }
 
 pub fn create_original_view(
-    video_location: &str,
+    video_context: &VideoContext,
     frame_number: u64,
     inode_number: u64,
 ) -> DirectoryFuseNode {
+    let view = get_default_view("original");
     create_frame_view(
-        "original",
-        video_location,
+        &view.name,
+        video_context,
         frame_number,
         &mut || create_directory_attributes(inode_number),
-        &|video_location, frame_number, image_type, _| {
-            get_frame_image(video_location, frame_number, image_type)
+        &|video_context, frame_number, output, configuration_holder| {
+            get_frame_image(
+                video_context.video_location.to_string(),
+                frame_number,
+                output,
+                configuration_holder.get_encoding(),
+            )
         },
+        Some(view.parser),
+        view.default_configuration.clone(),
         None,
-        ConfigurationHolder::None,
     )
 }
 
 pub fn create_greyscale_view(
-    video_location: &str,
+    video_context: &VideoContext,
     frame_number: u64,
     inode_number: u64,
 ) -> DirectoryFuseNode {
+    let view = get_default_view("greyscale");
     create_frame_view(
-        "greyscale",
-        video_location,
+        &view.name,
+        video_context,
         frame_number,
         &mut || create_directory_attributes(inode_number),
-        &|video_location, frame_number, image_type, _| {
-            get_greyscale_frame_image(video_location, frame_number, image_type)
+        &|video_context, frame_number, output, configuration_holder| {
+            get_greyscale_frame_image(
+                video_context.video_location.to_string(),
+                frame_number,
+                output,
+                configuration_holder.get_encoding(),
+            )
         },
+        Some(view.parser),
+        view.default_configuration.clone(),
         None,
-        ConfigurationHolder::None,
     )
 }
 
 pub fn create_black_and_white_view(
-    video_location: &str,
+    video_context: &VideoContext,
     frame_number: u64,
     inode_number: u64,
 ) -> DirectoryFuseNode {
+    let view = get_default_view("black-and-white");
     create_frame_view(
-        "black-and-white",
-        video_location,
+        &view.name,
+        video_context,
         frame_number,
         &mut || create_directory_attributes(inode_number),
-        &|video_location, frame_number, image_type, configuration_holder| {
-            let threshold = match configuration_holder {
-                ConfigurationHolder::BlackAndWhite(x) => x.threshold,
+        &|video_context, frame_number, output, configuration_holder| {
+            let parameters = match &configuration_holder {
+                ConfigurationHolder::BlackAndWhite(x) => x
+                    .thresholding
+                    .get_threshold_parameters()
+                    .expect("Invalid black and white configuration"),
                 _ => panic!("Incorrect configuration type"),
             };
-            get_black_and_white_frame_image(video_location, frame_number, threshold, image_type)
+            get_black_and_white_frame_image(
+                video_context.video_location.to_string(),
+                frame_number,
+                parameters,
+                output,
+                configuration_holder.get_encoding(),
+            )
         },
-        Some(&|data| match BlackAndWhiteConfiguration::from_yaml(data) {
-            Ok(x) => Ok(ConfigurationHolder::BlackAndWhite(x)),
-            Err(e) => Err(e),
+        Some(view.parser),
+        view.default_configuration.clone(),
+        Some(&|video_context, frame_number, configuration_holder| {
+            let parameters = match &configuration_holder {
+                ConfigurationHolder::BlackAndWhite(x) => x
+                    .thresholding
+                    .get_threshold_parameters()
+                    .expect("Invalid black and white configuration"),
+                _ => panic!("Incorrect configuration type"),
+            };
+            ComputedThreshold {
+                method: parameters.method,
+                threshold: get_black_and_white_threshold(
+                    video_context.video_location.to_string(),
+                    frame_number,
+                    parameters,
+                ),
+            }
+            .to_yaml()
+            .unwrap()
+            .into_bytes()
         }),
-        ConfigurationHolder::BlackAndWhite(BlackAndWhiteConfiguration::default()),
     )
 }
 
+pub fn create_cropped_view(
+    video_context: &VideoContext,
+    frame_number: u64,
+    inode_number: u64,
+) -> DirectoryFuseNode {
+    let view = get_default_view("cropped");
+    create_frame_view(
+        &view.name,
+        video_context,
+        frame_number,
+        &mut || create_directory_attributes(inode_number),
+        &|video_context, frame_number, output, configuration_holder| {
+            let region = match &configuration_holder {
+                ConfigurationHolder::Cropped(x) => get_crop_region(x, video_context),
+                _ => panic!("Incorrect configuration type"),
+            }
+            .expect("Invalid crop configuration");
+            get_cropped_frame_image(
+                video_context.video_location.to_string(),
+                frame_number,
+                region,
+                output,
+                configuration_holder.get_encoding(),
+            )
+        },
+        Some(view.parser),
+        view.default_configuration.clone(),
+        None,
+    )
+}
+
+pub fn create_resized_view(
+    video_context: &VideoContext,
+    frame_number: u64,
+    inode_number: u64,
+) -> DirectoryFuseNode {
+    let view = get_default_view("resized");
+    create_frame_view(
+        &view.name,
+        video_context,
+        frame_number,
+        &mut || create_directory_attributes(inode_number),
+        &|video_context, frame_number, output, configuration_holder| {
+            let configuration = match &configuration_holder {
+                ConfigurationHolder::Resized(x) => x,
+                _ => panic!("Incorrect configuration type"),
+            };
+            get_resized_frame_image(
+                video_context.video_location.to_string(),
+                frame_number,
+                (configuration.resizing.width, configuration.resizing.height),
+                configuration.resizing.fit,
+                configuration.resizing.interpolation,
+                output,
+                &configuration.encoding,
+            )
+        },
+        Some(view.parser),
+        view.default_configuration.clone(),
+        None,
+    )
+}
+
+pub fn create_edges_view(
+    video_context: &VideoContext,
+    frame_number: u64,
+    inode_number: u64,
+) -> DirectoryFuseNode {
+    let view = get_default_view("edges");
+    create_frame_view(
+        &view.name,
+        video_context,
+        frame_number,
+        &mut || create_directory_attributes(inode_number),
+        &|video_context, frame_number, output, configuration_holder| {
+            let parameters = match &configuration_holder {
+                ConfigurationHolder::Edges(x) => x
+                    .edge_detection
+                    .get_edge_parameters()
+                    .expect("Invalid edges configuration"),
+                _ => panic!("Incorrect configuration type"),
+            };
+            get_edges_frame_image(
+                video_context.video_location.to_string(),
+                frame_number,
+                parameters,
+                output,
+                configuration_holder.get_encoding(),
+            )
+        },
+        Some(view.parser),
+        view.default_configuration.clone(),
+        None,
+    )
+}
+
+pub fn create_pipeline_view(
+    video_context: &VideoContext,
+    frame_number: u64,
+    inode_number: u64,
+    pipeline_view: &PipelineViewDefinition,
+) -> DirectoryFuseNode {
+    create_frame_view(
+        &pipeline_view.name,
+        video_context,
+        frame_number,
+        &mut || create_directory_attributes(inode_number),
+        &|video_context, frame_number, output, configuration_holder| {
+            let configuration = match &configuration_holder {
+                ConfigurationHolder::Pipeline(x) => x,
+                _ => panic!("Incorrect configuration type"),
+            };
+            get_pipeline_frame_image(
+                video_context.video_location.to_string(),
+                frame_number,
+                &configuration
+                    .get_operations()
+                    .expect("Invalid pipeline configuration"),
+                output,
+                &configuration.encoding,
+            )
+        },
+        Some(&parse_pipeline_configuration),
+        ConfigurationHolder::Pipeline(pipeline_view.configuration.clone()),
+        None,
+    )
+}
+
+fn get_crop_region(
+    configuration: &CroppedConfiguration,
+    video_context: &VideoContext,
+) -> Result<Region, String> {
+    configuration.get_region(
+        video_context.video_properties.width,
+        video_context.video_properties.height,
+    )
+}
+
+fn parse_basic_configuration(
+    data: &str,
+    _video_context: &VideoContext,
+) -> Result<ConfigurationHolder, String> {
+    Ok(ConfigurationHolder::Basic(BasicConfiguration::from_yaml(
+        data,
+    )?))
+}
+
+fn parse_black_and_white_configuration(
+    data: &str,
+    _video_context: &VideoContext,
+) -> Result<ConfigurationHolder, String> {
+    Ok(ConfigurationHolder::BlackAndWhite(
+        BlackAndWhiteConfiguration::from_yaml(data)?,
+    ))
+}
+
+fn parse_cropped_configuration(
+    data: &str,
+    video_context: &VideoContext,
+) -> Result<ConfigurationHolder, String> {
+    let configuration = CroppedConfiguration::from_yaml(data)?;
+    // Rejecting crops that are not within the frame
+    get_crop_region(&configuration, video_context)?;
+    Ok(ConfigurationHolder::Cropped(configuration))
+}
+
+fn parse_resized_configuration(
+    data: &str,
+    _video_context: &VideoContext,
+) -> Result<ConfigurationHolder, String> {
+    Ok(ConfigurationHolder::Resized(
+        ResizedConfiguration::from_yaml(data)?,
+    ))
+}
+
+fn parse_edges_configuration(
+    data: &str,
+    _video_context: &VideoContext,
+) -> Result<ConfigurationHolder, String> {
+    Ok(ConfigurationHolder::Edges(EdgesConfiguration::from_yaml(
+        data,
+    )?))
+}
+
+fn parse_pipeline_configuration(
+    data: &str,
+    _video_context: &VideoContext,
+) -> Result<ConfigurationHolder, String> {
+    Ok(ConfigurationHolder::Pipeline(
+        PipelineConfiguration::from_yaml(data)?,
+    ))
+}
+
+// Gets the views of the video that can be configured, which are the default views and the
+// user-declared views
+fn get_configurable_views(video_context: &VideoContext) -> Vec<ConfigurableView> {
+    let mut configurable_views: Vec<ConfigurableView> = DEFAULT_VIEWS
+        .iter()
+        .map(|x| x.configurable_view.clone())
+        .collect();
+    for pipeline_view in video_context.pipeline_views.iter() {
+        configurable_views.push(ConfigurableView::new(
+            &pipeline_view.name,
+            &parse_pipeline_configuration,
+            ConfigurationHolder::Pipeline(pipeline_view.configuration.clone()),
+        ));
+    }
+    configurable_views
+}
+
+// Inserts the directory of the video-wide configurations of views, which frames inherit unless
+// configured themselves
+fn insert_video_configuration_nodes(
+    node_store: &mut FuseNodeStore,
+    video_context: &VideoContext,
+    directory_inode_number: u64,
+) -> u64 {
+    let configuration_directory_inode_number = node_store
+        .create_and_insert_directory(VIDEO_CONFIGURATION_DIRECTORY_NAME, directory_inode_number);
+
+    for configurable_view in get_configurable_views(video_context) {
+        let movable_configurations = video_context.configurations.clone();
+        let movable_view_name = configurable_view.name.clone();
+        let movable_default_configuration = configurable_view.default_configuration.clone();
+        let data_fetcher = Box::new(move || {
+            movable_configurations
+                .get_video(&movable_view_name)
+                .unwrap_or_else(|| movable_default_configuration.clone())
+                .to_yaml()
+                .unwrap()
+                .into_bytes()
+        });
+
+        let movable_configurations = video_context.configurations.clone();
+        let movable_view_name = configurable_view.name.clone();
+        let movable_default_configuration = configurable_view.default_configuration.clone();
+        let movable_video_context = video_context.clone();
+        let configuration_parser = configurable_view.parser;
+        let config_change_handler = Box::new(move |data: &str| {
+            debug!("Received updated video-wide configuration: {}", data);
+            if is_empty_configuration(data) {
+                movable_configurations.remove_video(&movable_view_name);
+                movable_video_context.clear_configuration(None, &movable_view_name);
+                info!("Cleared {} configuration for video", movable_view_name);
+                return Ok(());
+            }
+            let configuration = configuration_parser(data, &movable_video_context)?;
+            movable_configurations.set_video(&movable_view_name, configuration.clone());
+            movable_video_context.save_configuration(
+                None,
+                &movable_view_name,
+                &configuration,
+                &movable_default_configuration,
+            );
+            info!(
+                "Updated {} configuration for video: {:?}",
+                movable_view_name, configuration
+            );
+            Ok(())
+        });
+
+        node_store.create_and_insert_file(
+            FileInformation::new_writable(
+                &format!("{}.yml", configurable_view.name),
+                data_fetcher,
+                config_change_handler,
+            ),
+            configuration_directory_inode_number,
+        );
+    }
+
+    configuration_directory_inode_number
+}
+
+// Whether written configuration data is empty (other than comments), which clears the configuration
+fn is_empty_configuration(data: &str) -> bool {
+    data.trim().is_empty()
+        || matches!(
+            serde_yaml::from_str::<serde_yaml::Value>(data),
+            Ok(serde_yaml::Value::Null)
+        )
+}
+
+// Describes where the configuration of a frame's view came from, as a comment to go at the top of
+// the view's `config.yml`
+fn describe_configuration_source(source: ConfigurationSource, view_name: &str) -> String {
+    match source {
+        ConfigurationSource::Frame => "# Configured for this frame\n".to_string(),
+        ConfigurationSource::Video => format!(
+            "# Inherited from the video-wide configuration: by-frame/{}/{}.yml\n",
+            VIDEO_CONFIGURATION_DIRECTORY_NAME, view_name
+        ),
+        ConfigurationSource::Default => format!(
+            "# Default configuration (which can be set for all frames in: by-frame/{}/{}.yml)\n",
+            VIDEO_CONFIGURATION_DIRECTORY_NAME, view_name
+        ),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn create_frame_view(
     view_name: &str,
-    video_location: &str,
+    video_context: &VideoContext,
     frame_number: u64,
     directory_attributes_generator: &mut dyn FnMut() -> FileAttr,
-    image_data_generator: &'static dyn Fn(String, u64, ImageType, ConfigurationHolder) -> Vec<u8>,
-    configuration_parser: Option<&'static dyn Fn(&str) -> Result<ConfigurationHolder, String>>,
+    image_data_generator: &'static ImageDataGenerator,
+    configuration_parser: Option<&'static ConfigurationParser>,
     default_configuration: ConfigurationHolder,
+    computed_data_generator: Option<&'static ComputedDataGenerator>,
 ) -> DirectoryFuseNode {
-    let video_location = video_location.to_string();
+    let video_context = video_context.clone();
+    let configurations = video_context.configurations.clone();
+    let frame_name = video_context.get_frame_name(frame_number);
     let view_name = view_name.to_string();
 
     DirectoryFuseNode::new(
@@ -135,27 +979,70 @@ pub fn create_frame_view(
         Box::new(move |_| {
             let mut directory_manifest = DirectoryManifest::new();
             let mut file_informations = vec![];
-            let configuration_holder = Arc::new(RwLock::new(default_configuration.to_owned()));
 
             for image_type in ImageType::iter() {
-                let file_name = format!("frame-{}.{}", frame_number, image_type.to_string());
-                let movable_configuration_holder = configuration_holder.clone();
-                let movable_video_location = video_location.to_string();
-
-                file_informations.push(FileInformation::new(
-                    &file_name,
-                    Box::new(move || {
-                        image_data_generator(
-                            movable_video_location.to_string(),
+                let file_name = format!("{}.{}", frame_name, image_type);
+                let listed = video_context.listing_mode.is_listed(image_type);
+                let mut outputs = vec![(file_name.clone(), FrameOutput::Image(image_type))];
+                if image_type.is_raw_buffer() {
+                    outputs.push((
+                        format!("{}.shape.yml", file_name),
+                        FrameOutput::RawShape(image_type),
+                    ));
+                }
+
+                for (output_file_name, output) in outputs {
+                    let movable_configurations = configurations.clone();
+                    let movable_view_name = view_name.to_string();
+                    let movable_default_configuration = default_configuration.clone();
+                    let movable_video_context = video_context.clone();
+                    // Note: the key identifies the image produced with the current configuration
+                    let get_key = Arc::new(move || {
+                        let (configuration, _) = movable_configurations.get_effective(
+                            frame_number,
+                            &movable_view_name,
+                            &movable_default_configuration,
+                        );
+                        let key = EncodedImageKey {
+                            video_location: movable_video_context.video_location.to_string(),
                             frame_number,
-                            image_type,
-                            movable_configuration_holder.read().unwrap().clone(),
+                            view: movable_view_name.to_string(),
+                            configuration: format!("{:?}", configuration),
+                            output,
+                        };
+                        (key, configuration)
+                    });
+
+                    let movable_get_key = get_key.clone();
+                    let movable_video_context = video_context.clone();
+                    file_informations.push(
+                        FileInformation::new(
+                            &output_file_name,
+                            Box::new(move || {
+                                let (key, configuration) = movable_get_key();
+                                FRAME_CACHE.get_encoded_image(key, || {
+                                    image_data_generator(
+                                        &movable_video_context,
+                                        frame_number,
+                                        output,
+                                        configuration,
+                                    )
+                                })
+                            }),
+                            listed,
+                            false,
                         )
-                    }),
-                    false,
-                    false,
-                ));
-                directory_manifest.add(image_type, &file_name);
+                        // Images are only encoded to get their size when they are read
+                        .with_size_fetcher(Box::new(move || {
+                            FRAME_CACHE
+                                .get_encoded_image_size(&get_key().0)
+                                .map(|x| x as u64)
+                        })),
+                    );
+                }
+                if video_context.listing_mode.is_in_manifest(image_type) {
+                    directory_manifest.add(image_type, &file_name);
+                }
             }
 
             file_informations.push(FileInformation::new_with_data(
@@ -176,39 +1063,175 @@ pub fn create_frame_view(
                 None,
             ));
 
-            // Required to use within inner closure
-            let configuration_parser = configuration_parser.clone();
-            let movable_configuration_holder = configuration_holder.clone();
+            if let Some(computed_data_generator) = computed_data_generator {
+                // The size of the data computed with a configuration, which is remembered so that it
+                // can be reported without processing the frame again
+                let computed_size: Arc<RwLock<Option<(String, u64)>>> = Arc::new(RwLock::new(None));
+                let movable_computed_size = computed_size.clone();
+                let get_configuration = {
+                    let movable_configurations = configurations.clone();
+                    let movable_view_name = view_name.to_string();
+                    let movable_default_configuration = default_configuration.clone();
+                    Arc::new(move || {
+                        movable_configurations
+                            .get_effective(
+                                frame_number,
+                                &movable_view_name,
+                                &movable_default_configuration,
+                            )
+                            .0
+                    })
+                };
+                let movable_get_configuration = get_configuration.clone();
+                let movable_video_context = video_context.clone();
+                file_informations.push(
+                    FileInformation::new(
+                        "computed.yml",
+                        Box::new(move || {
+                            let configuration = movable_get_configuration();
+                            let configuration_key = format!("{:?}", configuration);
+                            let data = computed_data_generator(
+                                &movable_video_context,
+                                frame_number,
+                                configuration,
+                            );
+                            *movable_computed_size.write().unwrap() =
+                                Some((configuration_key, data.len() as u64));
+                            data
+                        }),
+                        true,
+                        false,
+                    )
+                    // Note: the size is not known until the data has been computed with the
+                    // current configuration
+                    .with_size_fetcher(Box::new(move || {
+                        let configuration_key = format!("{:?}", get_configuration());
+                        match &*computed_size.read().unwrap() {
+                            Some((key, size)) if *key == configuration_key => Some(*size),
+                            _ => None,
+                        }
+                    })),
+                );
+            }
+
+            // Note: the configuration may have been set before (if the frame's nodes were evicted)
+            // or inherited from the video-wide configuration, which may change
+            let movable_configurations = configurations.clone();
             let movable_view_name = view_name.to_string();
-            // TODO: correctly handle unwrap
-            let config_change_handler: Option<Box<dyn Fn(&str) -> Result<(), String>>> =
-                Some(Box::new(move |data| {
-                    debug!("Received updated configuration: {}", data);
-
-                    // TODO: handle invalid config, don't just unwrap!
-                    let configuration = configuration_parser.as_ref().unwrap()(data)?;
-
-                    // Update configuration shared with data generators
-                    let mut configuration_holder = movable_configuration_holder.write().unwrap();
-                    *configuration_holder = configuration.clone();
-                    info!(
-                        "Updated {} configuration for frame {}: {:?}",
-                        movable_view_name, frame_number, configuration
-                    );
-                    Ok(())
-                }));
+            let movable_default_configuration = default_configuration.clone();
+            let configuration_data_fetcher = Box::new(move || {
+                let (configuration, source) = movable_configurations.get_effective(
+                    frame_number,
+                    &movable_view_name,
+                    &movable_default_configuration,
+                );
+                format!(
+                    "{}{}",
+                    describe_configuration_source(source, &movable_view_name),
+                    configuration.to_yaml().unwrap()
+                )
+                .into_bytes()
+            });
 
-            file_informations.push(FileInformation::new_with_data(
-                "config.yml",
-                match &default_configuration {
-                    ConfigurationHolder::BlackAndWhite(configuration) => {
-                        configuration.to_yaml().unwrap().into_bytes()
-                    }
-                    _ => vec![],
-                },
+            // Views that cannot be configured have a read-only configuration
+            let configuration_parser = match configuration_parser {
+                Some(x) => x,
+                None => {
+                    file_informations.push(FileInformation::new(
+                        "config.yml",
+                        configuration_data_fetcher,
+                        true,
+                        false,
+                    ));
+                    return file_informations;
+                }
+            };
+
+            // Why the last change to the configuration was rejected (if it was)
+            let configuration_error: Arc<RwLock<Option<String>>> = Arc::new(RwLock::new(None));
+            let movable_configuration_error = configuration_error.clone();
+            file_informations.push(FileInformation::new(
+                "config.error",
+                Box::new(
+                    move || match &*movable_configuration_error.read().unwrap() {
+                        Some(x) => format!("{}\n", x).into_bytes(),
+                        None => vec![],
+                    },
+                ),
                 true,
                 false,
-                true,
+            ));
+
+            // Required to use within inner closure
+            let movable_configurations = configurations.clone();
+            let movable_view_name = view_name.to_string();
+            let movable_default_configuration = default_configuration.clone();
+            let movable_video_context = video_context.clone();
+            let config_change_handler: Box<DataChangeHandler> = Box::new(move |data| {
+                debug!("Received updated configuration: {}", data);
+
+                // Images produced with the previous configuration are no longer required
+                let remove_encoded_images = || {
+                    FRAME_CACHE.remove_encoded_images(
+                        &movable_video_context.video_location,
+                        frame_number,
+                        &movable_view_name,
+                    )
+                };
+
+                let configuration = if is_empty_configuration(data) {
+                    None
+                } else {
+                    match configuration_parser(data, &movable_video_context) {
+                        Ok(x) => Some(x),
+                        Err(e) => {
+                            *configuration_error.write().unwrap() = Some(e.clone());
+                            return Err(e);
+                        }
+                    }
+                };
+                *configuration_error.write().unwrap() = None;
+
+                // Clearing the configuration, or setting it to the one that would be inherited,
+                // means that it is inherited again (including any later changes to it)
+                let inherited_configuration = movable_configurations
+                    .get_video(&movable_view_name)
+                    .unwrap_or_else(|| movable_default_configuration.clone());
+                let configuration = configuration
+                    .filter(|x| x.to_yaml().unwrap() != inherited_configuration.to_yaml().unwrap());
+                remove_encoded_images();
+                let configuration = match configuration {
+                    Some(x) => x,
+                    None => {
+                        movable_configurations.remove(frame_number, &movable_view_name);
+                        movable_video_context
+                            .clear_configuration(Some(frame_number), &movable_view_name);
+                        info!(
+                            "Cleared {} configuration for frame {}",
+                            movable_view_name, frame_number
+                        );
+                        return Ok(());
+                    }
+                };
+
+                // Update configuration shared with data generators
+                movable_configurations.set(frame_number, &movable_view_name, configuration.clone());
+                movable_video_context.save_configuration(
+                    Some(frame_number),
+                    &movable_view_name,
+                    &configuration,
+                    &inherited_configuration,
+                );
+                info!(
+                    "Updated {} configuration for frame {}: {:?}",
+                    movable_view_name, frame_number, configuration
+                );
+                Ok(())
+            });
+
+            file_informations.push(FileInformation::new_writable(
+                "config.yml",
+                configuration_data_fetcher,
                 config_change_handler,
             ));
 
@@ -216,3 +1239,86 @@ pub fn create_frame_view(
         }),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, 0, "frame-0"; "when 0-based")]
+    #[test_case(1, 0, "frame-1"; "when 1-based")]
+    #[test_case(1, 41, "frame-42"; "when 1-based later frame")]
+    fn frame_names(first_frame_number: u64, frame_number: u64, name: &str) {
+        let video_context = VideoContext::new(
+            "",
+            &VideoProperties::default(),
+            &VideoNodesOptions {
+                first_frame_number,
+                ..Default::default()
+            },
+        );
+        assert_eq!(video_context.get_frame_name(frame_number), name);
+        assert_eq!(video_context.parse_frame_name(name), Some(frame_number));
+    }
+
+    #[test_case(0, "frame-01"; "when leading zero")]
+    #[test_case(1, "frame-0"; "when before first frame")]
+    #[test_case(0, "frame-x"; "when not a number")]
+    #[test_case(0, "00-00-01.000"; "when not a frame")]
+    fn invalid_frame_names(first_frame_number: u64, name: &str) {
+        let video_context = VideoContext::new(
+            "",
+            &VideoProperties::default(),
+            &VideoNodesOptions {
+                first_frame_number,
+                ..Default::default()
+            },
+        );
+        assert_eq!(video_context.parse_frame_name(name), None);
+    }
+
+    #[test]
+    fn default_views_are_created_with_their_names() {
+        let video_context = VideoContext::new(
+            "",
+            &VideoProperties::default(),
+            &VideoNodesOptions::default(),
+        );
+        for default_view in DEFAULT_VIEWS.iter() {
+            let view = (default_view.generator)(&video_context, 0, 2);
+            assert_eq!(view.name, default_view.configurable_view.name);
+        }
+    }
+
+    #[test_case("", true; "when empty")]
+    #[test_case("# Configured for this frame\n", true; "when only comments")]
+    #[test_case("width: 64\n", false; "when configured")]
+    #[test_case("{}", false; "when empty mapping")]
+    fn empty_configurations(data: &str, expected: bool) {
+        assert_eq!(is_empty_configuration(data), expected);
+    }
+
+    #[test_case("lazy", Ok(ListingMode::Lazy); "when lazy")]
+    #[test_case("all", Ok(ListingMode::All); "when all")]
+    #[test_case("formats=png,jpg", Ok(ListingMode::Formats(vec![ImageType::PNG, ImageType::JPG])); "when formats")]
+    #[test_case("formats=png,gif", Err("Invalid image type: gif".to_string()); "when invalid format")]
+    #[test_case("formats=", Err("Invalid image type: ".to_string()); "when no formats")]
+    #[test_case("some", Err("Invalid listing mode: some".to_string()); "when invalid")]
+    fn listing_mode_parsing(value: &str, expected: Result<ListingMode, String>) {
+        assert_eq!(ListingMode::parse(value), expected);
+    }
+
+    #[test_case(ListingMode::Lazy, ImageType::PNG, false, true; "when lazy")]
+    #[test_case(ListingMode::All, ImageType::PNG, true, true; "when all")]
+    #[test_case(ListingMode::Formats(vec![ImageType::PNG]), ImageType::PNG, true, true; "when chosen format")]
+    #[test_case(ListingMode::Formats(vec![ImageType::PNG]), ImageType::JPG, false, false; "when other format")]
+    fn listing_mode(
+        listing_mode: ListingMode,
+        image_type: ImageType,
+        is_listed: bool,
+        is_in_manifest: bool,
+    ) {
+        assert_eq!(listing_mode.is_listed(image_type), is_listed);
+        assert_eq!(listing_mode.is_in_manifest(image_type), is_in_manifest);
+    }
+}
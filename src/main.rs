@@ -1,8 +1,8 @@
 #[macro_use]
 extern crate lazy_static;
-extern crate cached;
 extern crate log;
 extern crate serde;
+extern crate serde_json;
 extern crate serde_yaml;
 
 mod cli;
@@ -10,13 +10,18 @@ mod fuse_fs;
 mod video_processing;
 
 use crate::cli::{parse_configuration, Configuration};
-use crate::fuse_fs::create_video_filesystem;
 use crate::fuse_fs::fs::VideoFileSystem;
+use crate::fuse_fs::{
+    create_video_filesystem, load_pipeline_views, PipelineViewDefinition, StateFile,
+    VideoNodesOptions,
+};
+use crate::video_processing::cache::FRAME_CACHE;
 use log::{debug, error, info};
 use std::ffi::OsStr;
 use std::fs::{create_dir_all, File};
 use std::path::Path;
 use std::process::exit;
+use std::sync::Arc;
 
 use daemonize::Daemonize;
 use std::ops::Deref;
@@ -25,11 +30,32 @@ use std::{env, process};
 #[repr(i32)]
 enum StatusCode {
     InvalidVideoLocation = 10,
+    InvalidViews = 11,
+    InvalidStateFile = 12,
 }
 
 fn main() {
     let configuration = initialise();
-    let filesystem = create_video_filesystem(&configuration.video_location);
+    FRAME_CACHE.set_sizes(
+        configuration.decoded_cache_size,
+        configuration.encoded_cache_size,
+    );
+    let filesystem = match create_video_filesystem(
+        &configuration.video_locations,
+        &VideoNodesOptions {
+            first_frame_number: configuration.first_frame_number,
+            pipeline_views: load_views(&configuration),
+            state_file: load_state_file(&configuration),
+            listing_mode: configuration.listing_mode.clone(),
+        },
+        configuration.number_of_workers,
+    ) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Invalid video: {}", e);
+            exit(StatusCode::InvalidVideoLocation as i32);
+        }
+    };
     if !configuration.foreground {
         daemonize(
             configuration.log_location.as_ref().map(Deref::deref),
@@ -96,12 +122,42 @@ pub fn daemonize(stdout_location: Option<&str>, stderr_location: Option<&str>) {
     }
 }
 
+fn load_views(configuration: &Configuration) -> Vec<PipelineViewDefinition> {
+    let views_location = match &configuration.views_location {
+        None => return vec![],
+        Some(x) => x,
+    };
+    match load_pipeline_views(views_location) {
+        Ok(x) => {
+            info!("Loaded {} views from: {}", x.len(), views_location);
+            x
+        }
+        Err(e) => {
+            error!("Invalid views file {}: {}", views_location, e);
+            exit(StatusCode::InvalidViews as i32);
+        }
+    }
+}
+
+fn load_state_file(configuration: &Configuration) -> Option<Arc<StateFile>> {
+    let state_location = configuration.state_location.as_ref()?;
+    match StateFile::load(state_location) {
+        Ok(x) => {
+            info!("Loaded state from: {}", state_location);
+            Some(Arc::new(x))
+        }
+        Err(e) => {
+            error!("Invalid state file {}: {}", state_location, e);
+            exit(StatusCode::InvalidStateFile as i32);
+        }
+    }
+}
+
 fn validate_configuration(configuration: &Configuration) {
-    if !Path::new(configuration.video_location.as_str()).exists() {
-        error!(
-            "Video location does not exist: {}",
-            configuration.video_location
-        );
-        exit(StatusCode::InvalidVideoLocation as i32);
+    for video_location in &configuration.video_locations {
+        if !Path::new(video_location.as_str()).exists() {
+            error!("Video location does not exist: {}", video_location);
+            exit(StatusCode::InvalidVideoLocation as i32);
+        }
     }
 }
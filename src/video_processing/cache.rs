@@ -0,0 +1,502 @@
+use crate::video_processing::FrameOutput;
+use log::debug;
+use opencv::core::{Mat, MatTraitConst};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_DECODED_CACHE_SIZE: usize = 512 * 1024 * 1024;
+pub const DEFAULT_ENCODED_CACHE_SIZE: usize = 64 * 1024 * 1024;
+// Note: sizes are held for many more images than the images themselves, as they are much smaller
+const ENCODED_IMAGE_SIZES_CACHE_SIZE: usize = 16 * 1024 * 1024;
+const THRESHOLDS_CACHE_SIZE: usize = 16 * 1024 * 1024;
+
+lazy_static! {
+    pub static ref FRAME_CACHE: FrameCache =
+        FrameCache::new(DEFAULT_DECODED_CACHE_SIZE, DEFAULT_ENCODED_CACHE_SIZE);
+}
+
+pub trait ByteSize {
+    fn byte_size(&self) -> usize;
+}
+
+impl ByteSize for Vec<u8> {
+    fn byte_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl ByteSize for Arc<Mat> {
+    fn byte_size(&self) -> usize {
+        self.total() * self.elem_size().unwrap_or(0)
+    }
+}
+
+// The size of an encoded image, which is remembered after the image has been evicted so that the
+// sizes of files can be reported without encoding their images again
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct EncodedImageSize(usize);
+
+impl ByteSize for EncodedImageSize {
+    // Approximate memory used by an entry in the cache (including its key)
+    fn byte_size(&self) -> usize {
+        256
+    }
+}
+
+// The threshold used to convert a frame to black and white (if a single threshold was used for all
+// pixels), which is remembered alongside the converted frame so that it can be reported without
+// converting the frame again
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Threshold(Option<f64>);
+
+impl ByteSize for Threshold {
+    // Approximate memory used by an entry in the cache (including its key)
+    fn byte_size(&self) -> usize {
+        256
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DecodedFrameKey {
+    pub video_location: String,
+    pub frame_number: u64,
+    // Identifies the processing applied to the decoded frame (including any configuration), e.g.
+    // `black-and-white:Some(128)`
+    pub transformation: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EncodedImageKey {
+    pub video_location: String,
+    pub frame_number: u64,
+    pub view: String,
+    // Identifies the configuration of the view (including how images are encoded)
+    pub configuration: String,
+    pub output: FrameOutput,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CacheTierStatistics {
+    pub maximum_bytes: usize,
+    pub bytes: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CacheStatistics {
+    pub decoded: CacheTierStatistics,
+    pub encoded: CacheTierStatistics,
+}
+
+impl CacheStatistics {
+    pub fn to_yaml(&self) -> Result<String, String> {
+        match serde_yaml::to_string(self) {
+            Ok(x) => Ok(x),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+struct CacheEntry<V> {
+    value: V,
+    byte_size: usize,
+    last_used: u64,
+}
+
+// Least-recently-used cache that is bounded by the total size of the values it holds, rather than
+// by the number of values
+pub struct ByteSizeBoundedCache<K, V> {
+    maximum_bytes: usize,
+    bytes: usize,
+    entries: HashMap<K, CacheEntry<V>>,
+    // Keys of the entries, ordered by when they were last used
+    usage_order: BTreeMap<u64, K>,
+    uses: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone + ByteSize> ByteSizeBoundedCache<K, V> {
+    pub fn new(maximum_bytes: usize) -> Self {
+        ByteSizeBoundedCache {
+            maximum_bytes,
+            bytes: 0,
+            entries: HashMap::new(),
+            usage_order: BTreeMap::new(),
+            uses: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        self.uses += 1;
+        let entry = match self.entries.get_mut(key) {
+            Some(x) => x,
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+        self.hits += 1;
+        let key = self.usage_order.remove(&entry.last_used).unwrap();
+        entry.last_used = self.uses;
+        self.usage_order.insert(self.uses, key);
+        Some(entry.value.clone())
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.remove(&key);
+        let byte_size = value.byte_size();
+        if byte_size > self.maximum_bytes {
+            debug!(
+                "Not caching value of {} bytes, as it is larger than the cache ({} bytes)",
+                byte_size, self.maximum_bytes
+            );
+            return;
+        }
+        self.bytes += byte_size;
+        self.evict(self.maximum_bytes);
+
+        self.uses += 1;
+        self.usage_order.insert(self.uses, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                byte_size,
+                last_used: self.uses,
+            },
+        );
+    }
+
+    pub fn set_maximum_bytes(&mut self, maximum_bytes: usize) {
+        self.maximum_bytes = maximum_bytes;
+        self.evict(maximum_bytes);
+    }
+
+    pub fn get_statistics(&self) -> CacheTierStatistics {
+        CacheTierStatistics {
+            maximum_bytes: self.maximum_bytes,
+            bytes: self.bytes,
+            entries: self.entries.len(),
+            hits: self.hits,
+            misses: self.misses,
+        }
+    }
+
+    pub fn remove_where(&mut self, predicate: impl Fn(&K) -> bool) {
+        let keys: Vec<K> = self
+            .entries
+            .keys()
+            .filter(|x| predicate(x))
+            .cloned()
+            .collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.usage_order.remove(&entry.last_used);
+            self.bytes -= entry.byte_size;
+        }
+    }
+
+    // Evicts least recently used entries until the cache is within the given size. Note: `bytes`
+    // may include the size of a value that is about to be inserted.
+    fn evict(&mut self, maximum_bytes: usize) {
+        while self.bytes > maximum_bytes {
+            let key = match self.usage_order.values().next() {
+                Some(x) => x.clone(),
+                None => break,
+            };
+            self.remove(&key);
+        }
+    }
+}
+
+// Cache of frames, with decoded (and processed) frames and encoded images held in separate tiers as
+// their sizes differ by orders of magnitude
+pub struct FrameCache {
+    decoded_frames: Mutex<ByteSizeBoundedCache<DecodedFrameKey, Arc<Mat>>>,
+    encoded_images: Mutex<ByteSizeBoundedCache<EncodedImageKey, Vec<u8>>>,
+    encoded_image_sizes: Mutex<ByteSizeBoundedCache<EncodedImageKey, EncodedImageSize>>,
+    thresholds: Mutex<ByteSizeBoundedCache<DecodedFrameKey, Threshold>>,
+}
+
+impl FrameCache {
+    pub fn new(decoded_cache_size: usize, encoded_cache_size: usize) -> Self {
+        FrameCache {
+            decoded_frames: Mutex::new(ByteSizeBoundedCache::new(decoded_cache_size)),
+            encoded_images: Mutex::new(ByteSizeBoundedCache::new(encoded_cache_size)),
+            encoded_image_sizes: Mutex::new(ByteSizeBoundedCache::new(
+                ENCODED_IMAGE_SIZES_CACHE_SIZE,
+            )),
+            thresholds: Mutex::new(ByteSizeBoundedCache::new(THRESHOLDS_CACHE_SIZE)),
+        }
+    }
+
+    pub fn set_sizes(&self, decoded_cache_size: usize, encoded_cache_size: usize) {
+        self.decoded_frames
+            .lock()
+            .unwrap()
+            .set_maximum_bytes(decoded_cache_size);
+        self.encoded_images
+            .lock()
+            .unwrap()
+            .set_maximum_bytes(encoded_cache_size);
+    }
+
+    pub fn get_decoded_frame(
+        &self,
+        key: DecodedFrameKey,
+        decoder: impl FnOnce() -> Mat,
+    ) -> Arc<Mat> {
+        get_or_insert(&self.decoded_frames, key, || Arc::new(decoder()))
+    }
+
+    // Records the threshold used to produce the black and white frame with the given key
+    pub fn set_threshold(&self, key: DecodedFrameKey, threshold: Option<f64>) {
+        self.thresholds
+            .lock()
+            .unwrap()
+            .insert(key, Threshold(threshold));
+    }
+
+    // Gets the threshold used to produce a black and white frame (if it has been produced)
+    pub fn get_threshold(&self, key: &DecodedFrameKey) -> Option<Option<f64>> {
+        self.thresholds.lock().unwrap().get(key).map(|x| x.0)
+    }
+
+    pub fn get_encoded_image(
+        &self,
+        key: EncodedImageKey,
+        encoder: impl FnOnce() -> Vec<u8>,
+    ) -> Vec<u8> {
+        let image = get_or_insert(&self.encoded_images, key.clone(), encoder);
+        self.encoded_image_sizes
+            .lock()
+            .unwrap()
+            .insert(key, EncodedImageSize(image.len()));
+        image
+    }
+
+    // Gets the size of an image that has been encoded (if known), without encoding it
+    pub fn get_encoded_image_size(&self, key: &EncodedImageKey) -> Option<usize> {
+        self.encoded_image_sizes
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|x| x.0)
+    }
+
+    // Removes the encoded images of a view of a frame, e.g. when the view's configuration changes
+    pub fn remove_encoded_images(&self, video_location: &str, frame_number: u64, view: &str) {
+        let predicate = |key: &EncodedImageKey| {
+            key.video_location == video_location
+                && key.frame_number == frame_number
+                && key.view == view
+        };
+        self.encoded_images.lock().unwrap().remove_where(predicate);
+        self.encoded_image_sizes
+            .lock()
+            .unwrap()
+            .remove_where(predicate);
+    }
+
+    // Removes the decoded frames and encoded images of a video, e.g. when the video has changed
+    pub fn remove_video(&self, video_location: &str) {
+        self.decoded_frames
+            .lock()
+            .unwrap()
+            .remove_where(|key| key.video_location == video_location);
+        self.thresholds
+            .lock()
+            .unwrap()
+            .remove_where(|key| key.video_location == video_location);
+        let predicate = |key: &EncodedImageKey| key.video_location == video_location;
+        self.encoded_images.lock().unwrap().remove_where(predicate);
+        self.encoded_image_sizes
+            .lock()
+            .unwrap()
+            .remove_where(predicate);
+    }
+
+    pub fn get_statistics(&self) -> CacheStatistics {
+        CacheStatistics {
+            decoded: self.decoded_frames.lock().unwrap().get_statistics(),
+            encoded: self.encoded_images.lock().unwrap().get_statistics(),
+        }
+    }
+}
+
+// Note: the lock is not held whilst the value is produced, so concurrent misses of the same key may
+//       both produce the value
+fn get_or_insert<K: Clone + Eq + Hash, V: Clone + ByteSize>(
+    cache: &Mutex<ByteSizeBoundedCache<K, V>>,
+    key: K,
+    producer: impl FnOnce() -> V,
+) -> V {
+    if let Some(value) = cache.lock().unwrap().get(&key) {
+        return value;
+    }
+    let value = producer();
+    cache.lock().unwrap().insert(key, value.clone());
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_processing::ImageType;
+
+    fn create_cache(maximum_bytes: usize) -> ByteSizeBoundedCache<u64, Vec<u8>> {
+        ByteSizeBoundedCache::new(maximum_bytes)
+    }
+
+    #[test]
+    fn get_when_empty() {
+        let mut cache = create_cache(10);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get_statistics().misses, 1);
+    }
+
+    #[test]
+    fn get_when_inserted() {
+        let mut cache = create_cache(10);
+        cache.insert(1, vec![1, 2, 3]);
+        assert_eq!(cache.get(&1), Some(vec![1, 2, 3]));
+        assert_eq!(
+            cache.get_statistics(),
+            CacheTierStatistics {
+                maximum_bytes: 10,
+                bytes: 3,
+                entries: 1,
+                hits: 1,
+                misses: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn insert_replaces() {
+        let mut cache = create_cache(10);
+        cache.insert(1, vec![1, 2, 3]);
+        cache.insert(1, vec![4]);
+        assert_eq!(cache.get(&1), Some(vec![4]));
+        assert_eq!(cache.get_statistics().bytes, 1);
+    }
+
+    #[test]
+    fn insert_evicts_least_recently_used() {
+        let mut cache = create_cache(10);
+        cache.insert(1, vec![0; 4]);
+        cache.insert(2, vec![0; 4]);
+        cache.get(&1);
+        cache.insert(3, vec![0; 4]);
+        assert!(cache.get(&1).is_some());
+        assert!(cache.get(&2).is_none());
+        assert!(cache.get(&3).is_some());
+        assert_eq!(cache.get_statistics().bytes, 8);
+    }
+
+    #[test]
+    fn insert_when_larger_than_cache() {
+        let mut cache = create_cache(10);
+        cache.insert(1, vec![0; 4]);
+        cache.insert(2, vec![0; 11]);
+        assert!(cache.get(&1).is_some());
+        assert!(cache.get(&2).is_none());
+    }
+
+    #[test]
+    fn set_maximum_bytes_evicts() {
+        let mut cache = create_cache(10);
+        cache.insert(1, vec![0; 4]);
+        cache.insert(2, vec![0; 4]);
+        cache.set_maximum_bytes(5);
+        assert!(cache.get(&1).is_none());
+        assert!(cache.get(&2).is_some());
+        assert_eq!(cache.get_statistics().maximum_bytes, 5);
+    }
+
+    #[test]
+    fn remove_where() {
+        let mut cache = create_cache(10);
+        cache.insert(1, vec![0; 4]);
+        cache.insert(2, vec![0; 4]);
+        cache.remove_where(|key| *key == 1);
+        assert!(cache.get(&1).is_none());
+        assert!(cache.get(&2).is_some());
+        assert_eq!(cache.get_statistics().bytes, 4);
+    }
+
+    #[test]
+    fn frame_cache_encoded_image() {
+        let frame_cache = FrameCache::new(0, 10);
+        let key = EncodedImageKey {
+            video_location: "video.mp4".to_string(),
+            frame_number: 1,
+            view: "original".to_string(),
+            configuration: "".to_string(),
+            output: FrameOutput::Image(ImageType::PNG),
+        };
+        assert_eq!(
+            frame_cache.get_encoded_image(key.clone(), || vec![1]),
+            vec![1]
+        );
+        assert_eq!(
+            frame_cache.get_encoded_image(key.clone(), || vec![2]),
+            vec![1]
+        );
+        frame_cache.remove_encoded_images("video.mp4", 1, "original");
+        assert_eq!(frame_cache.get_encoded_image(key, || vec![3]), vec![3]);
+
+        let statistics = frame_cache.get_statistics();
+        assert_eq!(statistics.encoded.hits, 1);
+        assert_eq!(statistics.encoded.misses, 2);
+        assert_eq!(statistics.decoded, CacheTierStatistics::default());
+    }
+
+    #[test]
+    fn frame_cache_encoded_image_size() {
+        let frame_cache = FrameCache::new(0, 0);
+        let key = EncodedImageKey {
+            video_location: "video.mp4".to_string(),
+            frame_number: 1,
+            view: "original".to_string(),
+            configuration: "".to_string(),
+            output: FrameOutput::Image(ImageType::PNG),
+        };
+        assert_eq!(frame_cache.get_encoded_image_size(&key), None);
+        frame_cache.get_encoded_image(key.clone(), || vec![1, 2, 3]);
+        // Note: the image itself is too large to be cached
+        assert_eq!(frame_cache.get_statistics().encoded.entries, 0);
+        assert_eq!(frame_cache.get_encoded_image_size(&key), Some(3));
+
+        frame_cache.remove_encoded_images("video.mp4", 1, "original");
+        assert_eq!(frame_cache.get_encoded_image_size(&key), None);
+    }
+
+    #[test]
+    fn frame_cache_threshold() {
+        let frame_cache = FrameCache::new(0, 0);
+        let key = DecodedFrameKey {
+            video_location: "video.mp4".to_string(),
+            frame_number: 1,
+            transformation: "black-and-white".to_string(),
+        };
+        assert_eq!(frame_cache.get_threshold(&key), None);
+        frame_cache.set_threshold(key.clone(), Some(127.0));
+        assert_eq!(frame_cache.get_threshold(&key), Some(Some(127.0)));
+
+        frame_cache.remove_video("video.mp4");
+        assert_eq!(frame_cache.get_threshold(&key), None);
+    }
+}
@@ -0,0 +1,212 @@
+use log::debug;
+use opencv::core::Mat;
+use opencv::prelude::{VideoCaptureTrait, VideoCaptureTraitConst};
+use opencv::videoio::{VideoCapture, CAP_PROP_POS_FRAMES};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+const MAXIMUM_IDLE_DECODERS: usize = 8;
+// Reading (and discarding) a few frames is cheaper than seeking, which has to go back to the
+// previous keyframe and decode forward from there
+const MAXIMUM_FRAMES_TO_READ_FORWARD: u64 = 32;
+
+lazy_static! {
+    pub static ref DECODER_POOL: DecoderPool = DecoderPool::new(MAXIMUM_IDLE_DECODERS);
+}
+
+#[derive(Debug, PartialEq)]
+enum ReadStrategy {
+    Seek,
+    ReadForward(u64),
+}
+
+struct Decoder {
+    video_location: String,
+    video_capture: VideoCapture,
+    // The frame number of the frame that will be decoded next
+    position: u64,
+}
+
+impl Decoder {
+    fn open(video_location: &str) -> Self {
+        debug!("Opening decoder for video: {}", video_location);
+        Decoder {
+            video_location: video_location.to_string(),
+            video_capture: open_video(video_location),
+            position: 0,
+        }
+    }
+
+    fn get_frame(&mut self, frame_number: u64) -> Mat {
+        match get_read_strategy(self.position, frame_number) {
+            ReadStrategy::Seek => {
+                self.video_capture
+                    .set(CAP_PROP_POS_FRAMES, frame_number as f64)
+                    .unwrap();
+            }
+            ReadStrategy::ReadForward(number_of_frames) => {
+                for _ in 0..number_of_frames {
+                    self.video_capture.grab().unwrap();
+                }
+            }
+        }
+        self.position = frame_number + 1;
+        get_next_frame(&mut self.video_capture)
+    }
+
+    fn close(self) {
+        debug!("Closing decoder for video: {}", self.video_location);
+        close_video(self.video_capture);
+    }
+}
+
+// Pool of open video decoders, which saves reopening the video for each frame and allows frames that
+// follow on from previously decoded frames to be read without seeking
+pub struct DecoderPool {
+    idle_decoders: Mutex<VecDeque<Decoder>>,
+    maximum_idle_decoders: usize,
+}
+
+impl DecoderPool {
+    pub fn new(maximum_idle_decoders: usize) -> Self {
+        DecoderPool {
+            idle_decoders: Default::default(),
+            maximum_idle_decoders,
+        }
+    }
+
+    pub fn get_frame(&self, video_location: &str, frame_number: u64) -> Mat {
+        let mut decoder = self.take_decoder(video_location, frame_number);
+        let frame = decoder.get_frame(frame_number);
+        self.return_decoder(decoder);
+        frame
+    }
+
+    // Takes the idle decoder that is best positioned to decode the given frame, opening a decoder if
+    // there are none idle for the video
+    fn take_decoder(&self, video_location: &str, frame_number: u64) -> Decoder {
+        let mut idle_decoders = self.idle_decoders.lock().unwrap();
+        let decoder_positions: Vec<Option<u64>> = idle_decoders
+            .iter()
+            .map(|decoder| {
+                if decoder.video_location == video_location {
+                    Some(decoder.position)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        match choose_decoder(&decoder_positions, frame_number) {
+            Some(index) => idle_decoders.remove(index).unwrap(),
+            None => {
+                drop(idle_decoders);
+                Decoder::open(video_location)
+            }
+        }
+    }
+
+    // Closes the idle decoders of a video, e.g. when the video has changed
+    pub fn remove_video(&self, video_location: &str) {
+        let mut idle_decoders = self.idle_decoders.lock().unwrap();
+        let (removed_decoders, retained_decoders) = idle_decoders
+            .drain(..)
+            .partition(|decoder| decoder.video_location == video_location);
+        *idle_decoders = retained_decoders;
+        drop(idle_decoders);
+        for decoder in removed_decoders {
+            decoder.close();
+        }
+    }
+
+    fn return_decoder(&self, decoder: Decoder) {
+        let mut idle_decoders = self.idle_decoders.lock().unwrap();
+        idle_decoders.push_front(decoder);
+        while idle_decoders.len() > self.maximum_idle_decoders {
+            idle_decoders.pop_back().unwrap().close();
+        }
+    }
+}
+
+pub(crate) fn open_video(file_name: &str) -> VideoCapture {
+    VideoCapture::from_file(file_name, 0).expect(&format!(
+        "Error creating VideoCapture from file: {}",
+        file_name
+    ))
+}
+
+// Opens a video, which may not be readable (unlike videos opened with `open_video`)
+pub(crate) fn try_open_video(file_name: &str) -> Result<VideoCapture, String> {
+    let video_capture = VideoCapture::from_file(file_name, 0)
+        .map_err(|e| format!("Error creating VideoCapture from file {}: {}", file_name, e))?;
+    if !video_capture.is_opened().unwrap_or(false) {
+        return Err(format!("Could not open video: {}", file_name));
+    }
+    Ok(video_capture)
+}
+
+pub(crate) fn close_video(mut video_capture: VideoCapture) {
+    video_capture.release().expect("Error closing VideoCapture");
+}
+
+fn get_next_frame(video_capture: &mut VideoCapture) -> Mat {
+    let mut frame = opencv::core::Mat::default();
+    video_capture.read(&mut frame).unwrap();
+    frame
+}
+
+fn get_read_strategy(position: u64, frame_number: u64) -> ReadStrategy {
+    if frame_number >= position && frame_number - position <= MAXIMUM_FRAMES_TO_READ_FORWARD {
+        ReadStrategy::ReadForward(frame_number - position)
+    } else {
+        ReadStrategy::Seek
+    }
+}
+
+// Chooses the index of the decoder (given as the positions of decoders of the video, `None` for
+// decoders of other videos) that is able to read the frame with the least work. Decoders that can
+// read forward to the frame are preferred, followed by the most recently used decoder.
+fn choose_decoder(decoder_positions: &[Option<u64>], frame_number: u64) -> Option<usize> {
+    let mut chosen: Option<(usize, u64)> = None;
+    for (index, position) in decoder_positions.iter().enumerate() {
+        let position = match position {
+            Some(x) => *x,
+            None => continue,
+        };
+        let cost = match get_read_strategy(position, frame_number) {
+            ReadStrategy::ReadForward(number_of_frames) => number_of_frames,
+            ReadStrategy::Seek => u64::MAX,
+        };
+        if chosen.is_none() || cost < chosen.unwrap().1 {
+            chosen = Some((index, cost));
+        }
+    }
+    chosen.map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, 0, ReadStrategy::ReadForward(0); "when first frame")]
+    #[test_case(10, 10, ReadStrategy::ReadForward(0); "when next frame")]
+    #[test_case(10, 12, ReadStrategy::ReadForward(2); "when shortly after")]
+    #[test_case(10, 10 + MAXIMUM_FRAMES_TO_READ_FORWARD + 1, ReadStrategy::Seek; "when long after")]
+    #[test_case(10, 9, ReadStrategy::Seek; "when before")]
+    fn read_strategy(position: u64, frame_number: u64, expected: ReadStrategy) {
+        assert_eq!(get_read_strategy(position, frame_number), expected);
+    }
+
+    #[test_case(&[], 10, None; "when no decoders")]
+    #[test_case(&[None, None], 10, None; "when no decoders for video")]
+    #[test_case(&[None, Some(100)], 10, Some(1); "when only decoder requires seek")]
+    #[test_case(&[Some(100), Some(5), Some(9)], 10, Some(2); "when closest decoder")]
+    #[test_case(&[Some(100), Some(200)], 10, Some(0); "when all require seek")]
+    fn chosen_decoder(
+        decoder_positions: &[Option<u64>],
+        frame_number: u64,
+        expected: Option<usize>,
+    ) {
+        assert_eq!(choose_decoder(decoder_positions, frame_number), expected);
+    }
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+// Operators used to detect edges in a (greyscale) frame
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Display, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum EdgeOperator {
+    // Thin edges, found using hysteresis thresholding of the gradient
+    #[default]
+    Canny,
+    // Gradient magnitude in the given direction(s)
+    Sobel,
+    // Magnitude of the second derivative
+    Laplacian,
+}
+
+// The direction(s) of the gradient found by the Sobel operator
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SobelDirection {
+    // Horizontal gradient (i.e. vertical edges)
+    X,
+    // Vertical gradient (i.e. horizontal edges)
+    Y,
+    // Both gradients, combined equally
+    #[default]
+    Both,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EdgeParameters {
+    pub operator: EdgeOperator,
+    // Only used by the Canny operator
+    pub low_threshold: f64,
+    pub high_threshold: f64,
+    // Aperture size of the operator
+    pub kernel_size: u32,
+    // Only used by the Sobel operator
+    pub direction: SobelDirection,
+    // Size of the Gaussian kernel the frame is blurred with before edges are detected (if any)
+    pub blur: Option<u32>,
+}
+
+// Gets the kernel sizes that the given operator supports
+pub fn get_valid_kernel_sizes(operator: EdgeOperator) -> &'static [u32] {
+    match operator {
+        EdgeOperator::Canny => &[3, 5, 7],
+        EdgeOperator::Sobel => &[1, 3, 5, 7],
+        EdgeOperator::Laplacian => &[1, 3, 5, 7],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("canny", EdgeOperator::Canny; "when canny")]
+    #[test_case("sobel", EdgeOperator::Sobel; "when sobel")]
+    #[test_case("laplacian", EdgeOperator::Laplacian; "when laplacian")]
+    fn operator_parsing(yaml: &str, expected: EdgeOperator) {
+        assert_eq!(
+            serde_yaml::from_str::<EdgeOperator>(yaml).unwrap(),
+            expected
+        );
+        assert_eq!(expected.to_string(), yaml);
+    }
+
+    #[test]
+    fn direction_parsing() {
+        assert_eq!(
+            serde_yaml::from_str::<SobelDirection>("y").unwrap(),
+            SobelDirection::Y
+        );
+        assert!(serde_yaml::from_str::<SobelDirection>("xy").is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::video_processing::ImageType;
+use opencv::imgcodecs::{IMWRITE_JPEG_QUALITY, IMWRITE_PNG_COMPRESSION, IMWRITE_WEBP_QUALITY};
+use serde::{Deserialize, Serialize};
+
+// Parameters used when encoding images, per image type. Encoder defaults are used for image types
+// without parameters.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct EncodingConfiguration {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jpg: Option<JpgEncodingConfiguration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub png: Option<PngEncodingConfiguration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webp: Option<WebpEncodingConfiguration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct JpgEncodingConfiguration {
+    // 0 to 100 (higher is better quality)
+    pub quality: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct PngEncodingConfiguration {
+    // 0 to 9 (higher is smaller but slower)
+    pub compression: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct WebpEncodingConfiguration {
+    // 1 to 100 (higher is better quality, with values above 100 being lossless)
+    pub quality: u8,
+}
+
+impl EncodingConfiguration {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(jpg) = &self.jpg {
+            validate_range("jpg quality", jpg.quality, 0, 100)?;
+        }
+        if let Some(png) = &self.png {
+            validate_range("png compression", png.compression, 0, 9)?;
+        }
+        if let Some(webp) = &self.webp {
+            validate_range("webp quality", webp.quality, 1, 101)?;
+        }
+        Ok(())
+    }
+
+    // Gets the parameters to pass to `imencode` (pairs of parameter ID and value)
+    pub fn get_parameters(&self, image_type: ImageType) -> Vec<i32> {
+        match image_type {
+            ImageType::JPG => match &self.jpg {
+                Some(x) => vec![IMWRITE_JPEG_QUALITY, x.quality as i32],
+                None => vec![],
+            },
+            ImageType::PNG => match &self.png {
+                Some(x) => vec![IMWRITE_PNG_COMPRESSION, x.compression as i32],
+                None => vec![],
+            },
+            ImageType::WEBP => match &self.webp {
+                Some(x) => vec![IMWRITE_WEBP_QUALITY, x.quality as i32],
+                None => vec![],
+            },
+            _ => vec![],
+        }
+    }
+}
+
+fn validate_range(name: &str, value: u8, minimum: u8, maximum: u8) -> Result<(), String> {
+    if value < minimum || value > maximum {
+        return Err(format!(
+            "{} must be between {} and {} (inclusive): {}",
+            name, minimum, maximum, value
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("{}", ImageType::JPG, vec![]; "when not configured")]
+    #[test_case("jpg: {quality: 95}", ImageType::JPG, vec![IMWRITE_JPEG_QUALITY, 95]; "when jpg")]
+    #[test_case("png: {compression: 1}", ImageType::PNG, vec![IMWRITE_PNG_COMPRESSION, 1]; "when png")]
+    #[test_case("webp: {quality: 80}", ImageType::WEBP, vec![IMWRITE_WEBP_QUALITY, 80]; "when webp")]
+    #[test_case("jpg: {quality: 95}", ImageType::PNG, vec![]; "when other type configured")]
+    fn parameters(yaml: &str, image_type: ImageType, expected: Vec<i32>) {
+        let configuration: EncodingConfiguration = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(configuration.get_parameters(image_type), expected);
+    }
+
+    #[test_case("jpg: {quality: 100}", true; "when jpg quality valid")]
+    #[test_case("jpg: {quality: 101}", false; "when jpg quality too high")]
+    #[test_case("png: {compression: 10}", false; "when png compression too high")]
+    #[test_case("webp: {quality: 0}", false; "when webp quality too low")]
+    fn validation(yaml: &str, valid: bool) {
+        let configuration: EncodingConfiguration = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(configuration.validate().is_ok(), valid);
+    }
+
+    #[test_case("jpeg: {quality: 95}"; "when unknown image type")]
+    #[test_case("jpg: {level: 95}"; "when unknown parameter")]
+    fn unknown_fields(yaml: &str) {
+        assert!(serde_yaml::from_str::<EncodingConfiguration>(yaml).is_err());
+    }
+}
@@ -0,0 +1,658 @@
+pub mod cache;
+mod decoder;
+pub mod edges;
+pub mod encoding;
+pub mod pipeline;
+mod raw;
+pub mod resize;
+pub mod threshold;
+
+use crate::video_processing::cache::{DecodedFrameKey, FRAME_CACHE};
+use crate::video_processing::decoder::{close_video, try_open_video, DECODER_POOL};
+use crate::video_processing::edges::{EdgeOperator, EdgeParameters, SobelDirection};
+use crate::video_processing::encoding::EncodingConfiguration;
+use crate::video_processing::pipeline::PipelineOperation;
+use crate::video_processing::raw::{frame_to_raw, get_raw_shape};
+use crate::video_processing::resize::{get_resize_dimensions, Fit, Interpolation};
+use crate::video_processing::threshold::{ThresholdMethod, ThresholdParameters};
+use log::{info, warn};
+use opencv::core::{
+    add_weighted, convert_scale_abs, Mat, MatTraitConst, Point, Rect, Size, Vector,
+    BORDER_CONSTANT, BORDER_DEFAULT, CV_16S,
+};
+use opencv::imgcodecs::imencode;
+use opencv::imgproc::{
+    adaptive_threshold, canny, cvt_color, dilate, erode, gaussian_blur, get_structuring_element,
+    laplacian, morphology_default_border_value, resize, sobel, threshold,
+    ADAPTIVE_THRESH_GAUSSIAN_C, ADAPTIVE_THRESH_MEAN_C, MORPH_RECT, THRESH_BINARY,
+    THRESH_BINARY_INV, THRESH_OTSU, THRESH_TRIANGLE,
+};
+use opencv::prelude::VectorToVec;
+use opencv::prelude::VideoCaptureTraitConst;
+use opencv::videoio::{
+    CAP_PROP_FOURCC, CAP_PROP_FPS, CAP_PROP_FRAME_COUNT, CAP_PROP_FRAME_HEIGHT,
+    CAP_PROP_FRAME_WIDTH,
+};
+use opencv::{imgproc, Error};
+use std::sync::Arc;
+use strum_macros::{Display, EnumIter};
+
+#[derive(EnumIter, Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
+pub enum ImageType {
+    #[strum(serialize = "jpg")]
+    JPG,
+    #[strum(serialize = "png")]
+    PNG,
+    #[strum(serialize = "bmp")]
+    BMP,
+    #[strum(serialize = "webp")]
+    WEBP,
+    #[strum(serialize = "rgb")]
+    RGB,
+    #[strum(serialize = "bgr")]
+    BGR,
+    #[strum(serialize = "gray")]
+    GRAY,
+    #[strum(serialize = "npy")]
+    NPY,
+}
+
+impl ImageType {
+    // Raw pixel buffers have no header, so are described by a separate shape
+    pub fn is_raw_buffer(&self) -> bool {
+        matches!(self, ImageType::RGB | ImageType::BGR | ImageType::GRAY)
+    }
+
+    fn is_encoded(&self) -> bool {
+        matches!(
+            self,
+            ImageType::JPG | ImageType::PNG | ImageType::BMP | ImageType::WEBP
+        )
+    }
+}
+
+// Data that can be produced from a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameOutput {
+    Image(ImageType),
+    // The shape of the frame as a raw buffer of the given type
+    RawShape(ImageType),
+}
+
+// Rectangular region of a frame, in pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: u64,
+    pub y: u64,
+    pub width: u64,
+    pub height: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VideoProperties {
+    pub number_of_frames: u64,
+    pub frames_per_second: f64,
+    pub width: u64,
+    pub height: u64,
+    pub fourcc: i32,
+}
+
+// Forgets the decoded frames, encoded images and open decoders of a video, e.g. when the video has
+// changed
+pub fn forget_video(video_location: &str) {
+    FRAME_CACHE.remove_video(video_location);
+    DECODER_POOL.remove_video(video_location);
+}
+
+// Gets the properties of a video, failing if the video cannot be read (e.g. if it is not a video or
+// is corrupt)
+pub fn get_video_properties(video_location: &str) -> Result<VideoProperties, String> {
+    let video_capture = try_open_video(video_location)?;
+    let get_property = |property: i32, property_name: &str| {
+        video_capture.get(property).map_err(|e| {
+            format!(
+                "Error getting {} for video {}: {}",
+                property_name, video_location, e
+            )
+        })
+    };
+    let video_properties = (|| {
+        Ok(VideoProperties {
+            number_of_frames: get_property(CAP_PROP_FRAME_COUNT, "number of frames")? as u64,
+            frames_per_second: get_property(CAP_PROP_FPS, "frames per second")?,
+            width: get_property(CAP_PROP_FRAME_WIDTH, "width")? as u64,
+            height: get_property(CAP_PROP_FRAME_HEIGHT, "height")? as u64,
+            fourcc: get_property(CAP_PROP_FOURCC, "FourCC")? as i32,
+        })
+    })();
+    close_video(video_capture);
+    video_properties
+}
+
+pub(crate) fn get_frame_image(
+    video_location: String,
+    frame_number: u64,
+    output: FrameOutput,
+    encoding: &EncodingConfiguration,
+) -> Vec<u8> {
+    get_frame_data(
+        &video_location,
+        frame_number,
+        "original",
+        output,
+        encoding,
+        None,
+    )
+}
+
+pub fn get_greyscale_frame_image(
+    video_location: String,
+    frame_number: u64,
+    output: FrameOutput,
+    encoding: &EncodingConfiguration,
+) -> Vec<u8> {
+    get_frame_data(
+        &video_location,
+        frame_number,
+        "greyscale",
+        output,
+        encoding,
+        Some(&|frame| {
+            frame_to_greyscale(frame).expect(&format!(
+                "Could not create greyscale copy of frame number {} in video: {}",
+                frame_number, video_location
+            ))
+        }),
+    )
+}
+
+pub fn get_black_and_white_frame_image(
+    video_location: String,
+    frame_number: u64,
+    parameters: ThresholdParameters,
+    output: FrameOutput,
+    encoding: &EncodingConfiguration,
+) -> Vec<u8> {
+    get_frame_data(
+        &video_location,
+        frame_number,
+        &get_black_and_white_key(&video_location, frame_number, &parameters).transformation,
+        output,
+        encoding,
+        Some(&|frame| {
+            produce_black_and_white_frame(&video_location, frame_number, &parameters, frame).0
+        }),
+    )
+}
+
+// Gets the threshold used when converting a frame to black and white, which is `None` if the
+// threshold varies across the frame (as with the adaptive methods)
+pub fn get_black_and_white_threshold(
+    video_location: String,
+    frame_number: u64,
+    parameters: ThresholdParameters,
+) -> Option<f64> {
+    if parameters.method.is_adaptive() {
+        return None;
+    }
+    let key = get_black_and_white_key(&video_location, frame_number, &parameters);
+    if let Some(threshold) = FRAME_CACHE.get_threshold(&key) {
+        return threshold;
+    }
+    // Note: the frame is only converted here if its images have not been produced (or the threshold
+    //       has since been evicted)
+    let frame = get_frame_from_video(&video_location, frame_number);
+    produce_black_and_white_frame(&video_location, frame_number, &parameters, &frame).1
+}
+
+fn get_black_and_white_key(
+    video_location: &str,
+    frame_number: u64,
+    parameters: &ThresholdParameters,
+) -> DecodedFrameKey {
+    DecodedFrameKey {
+        video_location: video_location.to_string(),
+        frame_number,
+        transformation: format!("black-and-white:{:?}", parameters),
+    }
+}
+
+// Converts a frame to black and white, recording the threshold used in the frame cache (keyed the
+// same as the converted frame) so that it can be reported without converting the frame again
+fn produce_black_and_white_frame(
+    video_location: &str,
+    frame_number: u64,
+    parameters: &ThresholdParameters,
+    frame: &Mat,
+) -> (Mat, Option<f64>) {
+    info!(
+        "Producing black and white frame {} from video \"{}\", thresholding with {:?}",
+        frame_number, video_location, parameters
+    );
+    let greyscale_frame = frame_to_greyscale(frame).expect(&format!(
+        "Could not create greyscale copy of frame number {} in video: {}",
+        frame_number, video_location
+    ));
+    let (black_and_white_frame, threshold) = frame_to_black_and_white(&greyscale_frame, parameters)
+        .expect("Could not convert to black and white");
+    FRAME_CACHE.set_threshold(
+        get_black_and_white_key(video_location, frame_number, parameters),
+        threshold,
+    );
+    (black_and_white_frame, threshold)
+}
+
+pub fn get_cropped_frame_image(
+    video_location: String,
+    frame_number: u64,
+    region: Region,
+    output: FrameOutput,
+    encoding: &EncodingConfiguration,
+) -> Vec<u8> {
+    get_frame_data(
+        &video_location,
+        frame_number,
+        &format!("cropped:{:?}", region),
+        output,
+        encoding,
+        Some(&|frame| {
+            crop_frame(frame, region).expect(&format!(
+                "Could not crop frame number {} in video {} to: {:?}",
+                frame_number, video_location, region
+            ))
+        }),
+    )
+}
+
+// Resizes frames to the given width and/or height (see `get_resize_dimensions`)
+pub fn get_resized_frame_image(
+    video_location: String,
+    frame_number: u64,
+    size: (Option<u64>, Option<u64>),
+    fit: Fit,
+    interpolation: Interpolation,
+    output: FrameOutput,
+    encoding: &EncodingConfiguration,
+) -> Vec<u8> {
+    get_frame_data(
+        &video_location,
+        frame_number,
+        &format!("resized:{:?},{:?},{:?}", size, fit, interpolation),
+        output,
+        encoding,
+        Some(&|frame| {
+            resize_frame(frame, size, fit, interpolation).expect(&format!(
+                "Could not resize frame number {} in video: {}",
+                frame_number, video_location
+            ))
+        }),
+    )
+}
+
+pub fn get_edges_frame_image(
+    video_location: String,
+    frame_number: u64,
+    parameters: EdgeParameters,
+    output: FrameOutput,
+    encoding: &EncodingConfiguration,
+) -> Vec<u8> {
+    get_frame_data(
+        &video_location,
+        frame_number,
+        &format!("edges:{:?}", parameters),
+        output,
+        encoding,
+        Some(&|frame| {
+            let greyscale_frame = frame_to_greyscale(frame).expect(&format!(
+                "Could not create greyscale copy of frame number {} in video: {}",
+                frame_number, video_location
+            ));
+            frame_to_edges(&greyscale_frame, &parameters).expect(&format!(
+                "Could not detect edges in frame number {} in video: {}",
+                frame_number, video_location
+            ))
+        }),
+    )
+}
+
+// Gets an image of a frame that has been through the given operations, in order
+pub fn get_pipeline_frame_image(
+    video_location: String,
+    frame_number: u64,
+    operations: &[PipelineOperation],
+    output: FrameOutput,
+    encoding: &EncodingConfiguration,
+) -> Vec<u8> {
+    get_frame_data(
+        &video_location,
+        frame_number,
+        &format!("pipeline:{:?}", operations),
+        output,
+        encoding,
+        Some(&|frame| {
+            let mut frame = frame.try_clone().expect("Could not copy frame");
+            for operation in operations {
+                frame = apply_operation(&frame, operation).expect(&format!(
+                    "Could not apply {:?} to frame number {} in video: {}",
+                    operation, frame_number, video_location
+                ));
+            }
+            frame
+        }),
+    )
+}
+
+pub fn frame_matrix_to_vec(
+    frame: &Mat,
+    convert_to: ImageType,
+    encoding: &EncodingConfiguration,
+) -> Vec<u8> {
+    let parameters = &Vector::<i32>::from_slice(&encoding.get_parameters(convert_to));
+    let buffer = &mut Vector::<u8>::new();
+
+    if frame.empty() {
+        warn!("Empty frame found");
+        return Vec::new();
+    }
+    if !convert_to.is_encoded() {
+        return frame_to_raw(frame, convert_to).expect(&format!(
+            "Could not convert frame into: {}",
+            convert_to.to_string()
+        ));
+    }
+    imencode(
+        &format!(".{}", convert_to.to_string()),
+        frame,
+        buffer,
+        parameters,
+    )
+    .expect(&format!(
+        "Could not encode image into: {}",
+        convert_to.to_string()
+    ));
+    buffer.to_vec()
+}
+
+// Gets data produced from a frame, which is processed with the given processor (if any). The
+// transformation must uniquely identify the processing (including its configuration), as processed
+// frames are cached.
+fn get_frame_data(
+    video_location: &str,
+    frame_number: u64,
+    transformation: &str,
+    output: FrameOutput,
+    encoding: &EncodingConfiguration,
+    processor: Option<&dyn Fn(&Mat) -> Mat>,
+) -> Vec<u8> {
+    let frame = match processor {
+        None => get_frame_from_video(video_location, frame_number),
+        Some(processor) => FRAME_CACHE.get_decoded_frame(
+            DecodedFrameKey {
+                video_location: video_location.to_string(),
+                frame_number,
+                transformation: transformation.to_string(),
+            },
+            || processor(&get_frame_from_video(video_location, frame_number)),
+        ),
+    };
+    match output {
+        FrameOutput::Image(image_type) => frame_matrix_to_vec(&frame, image_type, encoding),
+        FrameOutput::RawShape(image_type) => get_raw_shape(&frame, image_type)
+            .expect("Could not get shape of frame")
+            .to_yaml()
+            .unwrap()
+            .into_bytes(),
+    }
+}
+
+fn get_frame_from_video(video_location: &str, frame_number: u64) -> Arc<Mat> {
+    FRAME_CACHE.get_decoded_frame(
+        DecodedFrameKey {
+            video_location: video_location.to_string(),
+            frame_number,
+            transformation: "original".to_string(),
+        },
+        || DECODER_POOL.get_frame(video_location, frame_number),
+    )
+}
+
+fn frame_to_greyscale(frame: &Mat) -> Result<Mat, Error> {
+    // Note: frames may have already been converted (e.g. by an earlier step of a pipeline)
+    if frame.channels() == 1 {
+        return frame.try_clone();
+    }
+    let mut greyscale_frame = Mat::default();
+    match cvt_color(frame, &mut greyscale_frame, imgproc::COLOR_BGR2GRAY, 0) {
+        Ok(_) => Ok(greyscale_frame),
+        Err(e) => Err(e),
+    }
+}
+
+fn crop_frame(frame: &Mat, region: Region) -> Result<Mat, Error> {
+    let rectangle = Rect::new(
+        region.x as i32,
+        region.y as i32,
+        region.width as i32,
+        region.height as i32,
+    );
+    Mat::roi(frame, rectangle)?.try_clone()
+}
+
+fn resize_frame(
+    frame: &Mat,
+    size: (Option<u64>, Option<u64>),
+    fit: Fit,
+    interpolation: Interpolation,
+) -> Result<Mat, Error> {
+    let dimensions = get_resize_dimensions(
+        frame.cols() as u64,
+        frame.rows() as u64,
+        size.0,
+        size.1,
+        fit,
+    );
+    let mut resized_frame = Mat::default();
+    resize(
+        frame,
+        &mut resized_frame,
+        Size::new(dimensions.width as i32, dimensions.height as i32),
+        0.0,
+        0.0,
+        interpolation.to_opencv(),
+    )?;
+    match dimensions.crop {
+        None => Ok(resized_frame),
+        Some(region) => crop_frame(&resized_frame, region),
+    }
+}
+
+// Converts a greyscale frame to black and white, returning the threshold used (if a single threshold
+// was used for all pixels)
+fn frame_to_black_and_white(
+    frame: &Mat,
+    parameters: &ThresholdParameters,
+) -> Result<(Mat, Option<f64>), Error> {
+    let thresholding_type = match parameters.invert {
+        true => THRESH_BINARY_INV,
+        false => THRESH_BINARY,
+    };
+    let mut black_and_white_frame = Mat::default();
+
+    let adaptive_method = match parameters.method {
+        ThresholdMethod::AdaptiveMean => Some(ADAPTIVE_THRESH_MEAN_C),
+        ThresholdMethod::AdaptiveGaussian => Some(ADAPTIVE_THRESH_GAUSSIAN_C),
+        _ => None,
+    };
+    if let Some(adaptive_method) = adaptive_method {
+        adaptive_threshold(
+            frame,
+            &mut black_and_white_frame,
+            255.0,
+            adaptive_method,
+            thresholding_type,
+            parameters.block_size as i32,
+            parameters.c,
+        )?;
+        return Ok((black_and_white_frame, None));
+    }
+
+    let thresholding_type = match parameters.method {
+        ThresholdMethod::Otsu => thresholding_type | THRESH_OTSU,
+        ThresholdMethod::Triangle => thresholding_type | THRESH_TRIANGLE,
+        _ => thresholding_type,
+    };
+    match threshold(
+        frame,
+        &mut black_and_white_frame,
+        parameters.threshold as f64,
+        255.0,
+        thresholding_type,
+    ) {
+        Ok(x) => {
+            info!("Used threshold: {}", x);
+            Ok((black_and_white_frame, Some(x)))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Detects the edges in a greyscale frame, producing a greyscale frame where brighter pixels are
+// stronger edges
+fn frame_to_edges(frame: &Mat, parameters: &EdgeParameters) -> Result<Mat, Error> {
+    let blurred_frame;
+    let frame = match parameters.blur {
+        None => frame,
+        Some(blur) => {
+            blurred_frame = blur_frame(frame, blur, 0.0)?;
+            &blurred_frame
+        }
+    };
+    let kernel_size = parameters.kernel_size as i32;
+
+    let mut edges_frame = Mat::default();
+    match parameters.operator {
+        EdgeOperator::Canny => canny(
+            frame,
+            &mut edges_frame,
+            parameters.low_threshold,
+            parameters.high_threshold,
+            kernel_size,
+            false,
+        )?,
+        EdgeOperator::Sobel => {
+            // Note: the gradient is signed, so a signed depth is used to stop negative gradients
+            //       being lost, before taking the absolute values
+            let get_gradient = |dx: i32, dy: i32| -> Result<Mat, Error> {
+                let mut gradient = Mat::default();
+                sobel(
+                    frame,
+                    &mut gradient,
+                    CV_16S,
+                    dx,
+                    dy,
+                    kernel_size,
+                    1.0,
+                    0.0,
+                    BORDER_DEFAULT,
+                )?;
+                let mut absolute_gradient = Mat::default();
+                convert_scale_abs(&gradient, &mut absolute_gradient, 1.0, 0.0)?;
+                Ok(absolute_gradient)
+            };
+            match parameters.direction {
+                SobelDirection::X => edges_frame = get_gradient(1, 0)?,
+                SobelDirection::Y => edges_frame = get_gradient(0, 1)?,
+                SobelDirection::Both => add_weighted(
+                    &get_gradient(1, 0)?,
+                    0.5,
+                    &get_gradient(0, 1)?,
+                    0.5,
+                    0.0,
+                    &mut edges_frame,
+                    -1,
+                )?,
+            }
+        }
+        EdgeOperator::Laplacian => {
+            let mut second_derivative = Mat::default();
+            laplacian(
+                frame,
+                &mut second_derivative,
+                CV_16S,
+                kernel_size,
+                1.0,
+                0.0,
+                BORDER_DEFAULT,
+            )?;
+            convert_scale_abs(&second_derivative, &mut edges_frame, 1.0, 0.0)?;
+        }
+    }
+    Ok(edges_frame)
+}
+
+fn blur_frame(frame: &Mat, kernel_size: u32, sigma: f64) -> Result<Mat, Error> {
+    let mut blurred_frame = Mat::default();
+    gaussian_blur(
+        frame,
+        &mut blurred_frame,
+        Size::new(kernel_size as i32, kernel_size as i32),
+        sigma,
+        sigma,
+        BORDER_DEFAULT,
+    )?;
+    Ok(blurred_frame)
+}
+
+// Dilates (or erodes) a frame with a square kernel
+fn frame_to_morphed(
+    frame: &Mat,
+    kernel_size: u32,
+    iterations: u32,
+    is_dilation: bool,
+) -> Result<Mat, Error> {
+    let kernel = get_structuring_element(
+        MORPH_RECT,
+        Size::new(kernel_size as i32, kernel_size as i32),
+        Point::new(-1, -1),
+    )?;
+    let morph = if is_dilation { dilate } else { erode };
+    let mut morphed_frame = Mat::default();
+    morph(
+        frame,
+        &mut morphed_frame,
+        &kernel,
+        Point::new(-1, -1),
+        iterations as i32,
+        BORDER_CONSTANT,
+        morphology_default_border_value()?,
+    )?;
+    Ok(morphed_frame)
+}
+
+fn apply_operation(frame: &Mat, operation: &PipelineOperation) -> Result<Mat, Error> {
+    match *operation {
+        PipelineOperation::Greyscale => frame_to_greyscale(frame),
+        PipelineOperation::GaussianBlur { kernel_size, sigma } => {
+            blur_frame(frame, kernel_size, sigma)
+        }
+        PipelineOperation::Threshold(parameters) => {
+            Ok(frame_to_black_and_white(&frame_to_greyscale(frame)?, &parameters)?.0)
+        }
+        PipelineOperation::Edges(parameters) => {
+            frame_to_edges(&frame_to_greyscale(frame)?, &parameters)
+        }
+        PipelineOperation::Dilate {
+            kernel_size,
+            iterations,
+        } => frame_to_morphed(frame, kernel_size, iterations, true),
+        PipelineOperation::Erode {
+            kernel_size,
+            iterations,
+        } => frame_to_morphed(frame, kernel_size, iterations, false),
+        PipelineOperation::Resize {
+            size,
+            fit,
+            interpolation,
+        } => resize_frame(frame, size, fit, interpolation),
+    }
+}
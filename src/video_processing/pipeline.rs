@@ -0,0 +1,29 @@
+use crate::video_processing::edges::EdgeParameters;
+use crate::video_processing::resize::{Fit, Interpolation};
+use crate::video_processing::threshold::ThresholdParameters;
+
+// An operation applied to a frame, as a step of a pipeline of operations
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PipelineOperation {
+    Greyscale,
+    GaussianBlur {
+        kernel_size: u32,
+        // Standard deviation of the Gaussian (0 to calculate it from the kernel size)
+        sigma: f64,
+    },
+    Threshold(ThresholdParameters),
+    Edges(EdgeParameters),
+    Dilate {
+        kernel_size: u32,
+        iterations: u32,
+    },
+    Erode {
+        kernel_size: u32,
+        iterations: u32,
+    },
+    Resize {
+        size: (Option<u64>, Option<u64>),
+        fit: Fit,
+        interpolation: Interpolation,
+    },
+}
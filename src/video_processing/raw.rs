@@ -0,0 +1,217 @@
+use crate::video_processing::ImageType;
+use opencv::core::{
+    Mat, MatTraitConst, StsBadArg, CV_16S, CV_16U, CV_32F, CV_32S, CV_64F, CV_8S, CV_8U,
+};
+use opencv::imgproc::{
+    cvt_color, COLOR_BGR2GRAY, COLOR_BGR2RGB, COLOR_BGRA2BGR, COLOR_BGRA2GRAY, COLOR_BGRA2RGB,
+    COLOR_GRAY2BGR, COLOR_GRAY2RGB,
+};
+use opencv::Error;
+use serde::{Deserialize, Serialize};
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+// The length of the NPY preamble (magic, version and header length) and header must be a multiple
+// of this
+const NPY_ALIGNMENT: usize = 64;
+
+// Describes the layout of a raw pixel buffer, which is row-major with interleaved channels
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RawShape {
+    pub height: usize,
+    pub width: usize,
+    pub channels: usize,
+    pub dtype: String,
+}
+
+impl RawShape {
+    pub fn to_yaml(&self) -> Result<String, String> {
+        match serde_yaml::to_string(self) {
+            Ok(x) => Ok(x),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+// Gets the bytes of a frame as the given raw type. `.rgb`, `.bgr` and `.gray` are packed pixel
+// buffers, whereas `.npy` holds the frame as it is (i.e. with OpenCV's BGR channel order)
+pub fn frame_to_raw(frame: &Mat, image_type: ImageType) -> Result<Vec<u8>, Error> {
+    let frame = convert_channels(frame, image_type)?;
+    let data = get_data(&frame)?;
+    if image_type != ImageType::NPY {
+        return Ok(data);
+    }
+
+    let mut npy = create_npy_header(&get_numpy_descr(frame.depth())?, &get_shape(&frame));
+    npy.extend(data);
+    Ok(npy)
+}
+
+pub fn get_raw_shape(frame: &Mat, image_type: ImageType) -> Result<RawShape, Error> {
+    let frame = convert_channels(frame, image_type)?;
+    Ok(RawShape {
+        height: frame.rows() as usize,
+        width: frame.cols() as usize,
+        channels: frame.channels() as usize,
+        dtype: get_numpy_dtype(frame.depth())?.0.to_string(),
+    })
+}
+
+fn convert_channels(frame: &Mat, image_type: ImageType) -> Result<Mat, Error> {
+    match get_colour_conversion(frame.channels(), image_type) {
+        None => frame.try_clone(),
+        Some(conversion) => {
+            let mut converted_frame = Mat::default();
+            cvt_color(frame, &mut converted_frame, conversion, 0)?;
+            Ok(converted_frame)
+        }
+    }
+}
+
+// Gets the colour conversion required to get a frame with the given number of channels into the
+// layout of the raw type (`None` if no conversion is required)
+fn get_colour_conversion(channels: i32, image_type: ImageType) -> Option<i32> {
+    match (image_type, channels) {
+        (ImageType::RGB, 1) => Some(COLOR_GRAY2RGB),
+        (ImageType::RGB, 3) => Some(COLOR_BGR2RGB),
+        (ImageType::RGB, 4) => Some(COLOR_BGRA2RGB),
+        (ImageType::BGR, 1) => Some(COLOR_GRAY2BGR),
+        (ImageType::BGR, 4) => Some(COLOR_BGRA2BGR),
+        (ImageType::GRAY, 3) => Some(COLOR_BGR2GRAY),
+        (ImageType::GRAY, 4) => Some(COLOR_BGRA2GRAY),
+        _ => None,
+    }
+}
+
+fn get_data(frame: &Mat) -> Result<Vec<u8>, Error> {
+    if frame.empty() {
+        return Ok(Vec::new());
+    }
+    if frame.is_continuous() {
+        return Ok(frame.data_bytes()?.to_vec());
+    }
+    Ok(frame.try_clone()?.data_bytes()?.to_vec())
+}
+
+fn get_shape(frame: &Mat) -> Vec<usize> {
+    let mut shape = vec![frame.rows() as usize, frame.cols() as usize];
+    if frame.channels() > 1 {
+        shape.push(frame.channels() as usize);
+    }
+    shape
+}
+
+// Gets the NumPy name and type string (without byte order) of the given OpenCV depth
+fn get_numpy_dtype(depth: i32) -> Result<(&'static str, &'static str), Error> {
+    match depth {
+        CV_8U => Ok(("uint8", "u1")),
+        CV_8S => Ok(("int8", "i1")),
+        CV_16U => Ok(("uint16", "u2")),
+        CV_16S => Ok(("int16", "i2")),
+        CV_32S => Ok(("int32", "i4")),
+        CV_32F => Ok(("float32", "f4")),
+        CV_64F => Ok(("float64", "f8")),
+        _ => Err(Error::new(
+            StsBadArg,
+            format!("Unsupported frame depth: {}", depth),
+        )),
+    }
+}
+
+fn get_numpy_descr(depth: i32) -> Result<String, Error> {
+    let type_string = get_numpy_dtype(depth)?.1;
+    let byte_order = if type_string.ends_with('1') {
+        "|"
+    } else if cfg!(target_endian = "little") {
+        "<"
+    } else {
+        ">"
+    };
+    Ok(format!("{}{}", byte_order, type_string))
+}
+
+// Creates the preamble and header of a version 1.0 NPY file:
+// https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html
+fn create_npy_header(descr: &str, shape: &[usize]) -> Vec<u8> {
+    let shape = match shape.len() {
+        1 => format!("({},)", shape[0]),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    let preamble_length = NPY_MAGIC.len() + 2;
+    let padding =
+        (NPY_ALIGNMENT - (preamble_length + header.len() + 1) % NPY_ALIGNMENT) % NPY_ALIGNMENT;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    let mut npy = NPY_MAGIC.to_vec();
+    npy.extend((header.len() as u16).to_le_bytes());
+    npy.extend(header.into_bytes());
+    npy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(1, ImageType::RGB, Some(COLOR_GRAY2RGB); "when greyscale to rgb")]
+    #[test_case(3, ImageType::RGB, Some(COLOR_BGR2RGB); "when bgr to rgb")]
+    #[test_case(3, ImageType::BGR, None; "when bgr to bgr")]
+    #[test_case(1, ImageType::GRAY, None; "when greyscale to gray")]
+    #[test_case(3, ImageType::GRAY, Some(COLOR_BGR2GRAY); "when bgr to gray")]
+    #[test_case(3, ImageType::NPY, None; "when npy")]
+    fn colour_conversion(channels: i32, image_type: ImageType, expected: Option<i32>) {
+        assert_eq!(get_colour_conversion(channels, image_type), expected);
+    }
+
+    #[test_case(CV_8U, "|u1"; "when unsigned byte")]
+    #[test_case(CV_32F, "<f4"; "when float")]
+    fn numpy_descr(depth: i32, expected: &str) {
+        assert_eq!(get_numpy_descr(depth).unwrap(), expected);
+    }
+
+    #[test_case(&[1080, 1920, 3], "(1080, 1920, 3)"; "when colour")]
+    #[test_case(&[1080, 1920], "(1080, 1920)"; "when greyscale")]
+    #[test_case(&[5], "(5,)"; "when one dimension")]
+    fn npy_header(shape: &[usize], expected_shape: &str) {
+        let header = create_npy_header("|u1", shape);
+        assert_eq!(header.len() % NPY_ALIGNMENT, 0);
+        assert_eq!(&header[..8], NPY_MAGIC);
+
+        let header_length = u16::from_le_bytes([header[8], header[9]]) as usize;
+        assert_eq!(header_length, header.len() - 10);
+        let dictionary = std::str::from_utf8(&header[10..]).unwrap();
+        assert!(dictionary.ends_with('\n'));
+        assert_eq!(
+            dictionary.trim_end(),
+            format!(
+                "{{'descr': '|u1', 'fortran_order': False, 'shape': {}, }}",
+                expected_shape
+            )
+        );
+    }
+
+    #[test]
+    fn raw_shape_serialisation() {
+        let raw_shape = RawShape {
+            height: 2,
+            width: 3,
+            channels: 1,
+            dtype: "uint8".to_string(),
+        };
+        assert_eq!(
+            raw_shape.to_yaml().unwrap(),
+            "height: 2\nwidth: 3\nchannels: 1\ndtype: uint8\n"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::video_processing::Region;
+use opencv::imgproc::{INTER_AREA, INTER_CUBIC, INTER_LANCZOS4, INTER_LINEAR, INTER_NEAREST};
+use serde::{Deserialize, Serialize};
+
+// How a frame is fitted to a target size, when both a width and height are given
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    // Scaled to fit within the size, preserving aspect ratio
+    #[default]
+    Contain,
+    // Scaled to cover the size, preserving aspect ratio, with the overflow cropped (centrally)
+    Cover,
+    // Scaled to the size
+    Stretch,
+}
+
+// Interpolation methods, corresponding to OpenCV's `INTER_*` constants
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    Nearest,
+    #[default]
+    Linear,
+    Cubic,
+    Area,
+    Lanczos4,
+}
+
+impl Interpolation {
+    pub fn to_opencv(self) -> i32 {
+        match self {
+            Interpolation::Nearest => INTER_NEAREST,
+            Interpolation::Linear => INTER_LINEAR,
+            Interpolation::Cubic => INTER_CUBIC,
+            Interpolation::Area => INTER_AREA,
+            Interpolation::Lanczos4 => INTER_LANCZOS4,
+        }
+    }
+}
+
+// The size to scale a frame to and the region of the scaled frame to then crop to (if any)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResizeDimensions {
+    pub width: u64,
+    pub height: u64,
+    pub crop: Option<Region>,
+}
+
+// Gets how to resize a frame to the given width and/or height. If only one of width and height is
+// given, the other is set to preserve the aspect ratio of the frame.
+pub fn get_resize_dimensions(
+    frame_width: u64,
+    frame_height: u64,
+    width: Option<u64>,
+    height: Option<u64>,
+    fit: Fit,
+) -> ResizeDimensions {
+    let frame_width = frame_width.max(1);
+    let frame_height = frame_height.max(1);
+    let scale = |x: u64, scale: f64| ((x as f64 * scale).round() as u64).max(1);
+
+    let (width, height) = match (width, height) {
+        (None, None) => (frame_width, frame_height),
+        (Some(width), None) => (
+            width,
+            scale(frame_height, width as f64 / frame_width as f64),
+        ),
+        (None, Some(height)) => (
+            scale(frame_width, height as f64 / frame_height as f64),
+            height,
+        ),
+        (Some(width), Some(height)) => {
+            let width_scale = width as f64 / frame_width as f64;
+            let height_scale = height as f64 / frame_height as f64;
+            match fit {
+                Fit::Stretch => (width, height),
+                Fit::Contain => {
+                    let frame_scale = width_scale.min(height_scale);
+                    (
+                        scale(frame_width, frame_scale).min(width),
+                        scale(frame_height, frame_scale).min(height),
+                    )
+                }
+                Fit::Cover => {
+                    let frame_scale = width_scale.max(height_scale);
+                    let scaled_width = scale(frame_width, frame_scale).max(width);
+                    let scaled_height = scale(frame_height, frame_scale).max(height);
+                    return ResizeDimensions {
+                        width: scaled_width,
+                        height: scaled_height,
+                        crop: Some(Region {
+                            x: (scaled_width - width) / 2,
+                            y: (scaled_height - height) / 2,
+                            width,
+                            height,
+                        }),
+                    };
+                }
+            }
+        }
+    };
+    ResizeDimensions {
+        width,
+        height,
+        crop: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(None, None, Fit::Contain, (1920, 1080); "when no size")]
+    #[test_case(Some(960), None, Fit::Contain, (960, 540); "when width")]
+    #[test_case(None, Some(270), Fit::Stretch, (480, 270); "when height")]
+    #[test_case(Some(100), Some(100), Fit::Contain, (100, 56); "when contain")]
+    #[test_case(Some(100), Some(100), Fit::Stretch, (100, 100); "when stretch")]
+    #[test_case(Some(3840), Some(1000), Fit::Contain, (1778, 1000); "when contain larger")]
+    fn resize_dimensions(width: Option<u64>, height: Option<u64>, fit: Fit, expected: (u64, u64)) {
+        let dimensions = get_resize_dimensions(1920, 1080, width, height, fit);
+        assert_eq!((dimensions.width, dimensions.height), expected);
+        assert_eq!(dimensions.crop, None);
+    }
+
+    #[test]
+    fn resize_dimensions_when_cover() {
+        assert_eq!(
+            get_resize_dimensions(1920, 1080, Some(100), Some(100), Fit::Cover),
+            ResizeDimensions {
+                width: 178,
+                height: 100,
+                crop: Some(Region {
+                    x: 39,
+                    y: 0,
+                    width: 100,
+                    height: 100
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn fit_and_interpolation_parsing() {
+        assert_eq!(serde_yaml::from_str::<Fit>("cover").unwrap(), Fit::Cover);
+        assert_eq!(
+            serde_yaml::from_str::<Interpolation>("lanczos4").unwrap(),
+            Interpolation::Lanczos4
+        );
+        assert!(serde_yaml::from_str::<Interpolation>("bilinear").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+// How the white/black threshold of a (greyscale) frame is chosen
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Display, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum ThresholdMethod {
+    // The given threshold is used for all pixels
+    Global,
+    // A single threshold is calculated from the frame's histogram, using Otsu's method
+    Otsu,
+    // A threshold is calculated for each pixel, from the mean of its neighbourhood
+    AdaptiveMean,
+    // A threshold is calculated for each pixel, from the Gaussian-weighted sum of its neighbourhood
+    AdaptiveGaussian,
+    // A single threshold is calculated from the frame's histogram, using the triangle algorithm
+    Triangle,
+}
+
+impl ThresholdMethod {
+    pub fn is_adaptive(self) -> bool {
+        matches!(
+            self,
+            ThresholdMethod::AdaptiveMean | ThresholdMethod::AdaptiveGaussian
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThresholdParameters {
+    pub method: ThresholdMethod,
+    // Only used by the global method
+    pub threshold: u8,
+    // Only used by adaptive methods: the (odd) width of the neighbourhood of each pixel and the
+    // constant subtracted from the neighbourhood's mean/weighted sum
+    pub block_size: u32,
+    pub c: f64,
+    // Whether pixels above the threshold are black, rather than white
+    pub invert: bool,
+}
+
+// The threshold used when converting a frame to black and white, which is `None` if the threshold
+// varies across the frame
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ComputedThreshold {
+    pub method: ThresholdMethod,
+    pub threshold: Option<f64>,
+}
+
+impl ComputedThreshold {
+    pub fn to_yaml(&self) -> Result<String, String> {
+        match serde_yaml::to_string(self) {
+            Ok(x) => Ok(x),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("global", ThresholdMethod::Global; "when global")]
+    #[test_case("adaptive-mean", ThresholdMethod::AdaptiveMean; "when adaptive mean")]
+    #[test_case("adaptive-gaussian", ThresholdMethod::AdaptiveGaussian; "when adaptive gaussian")]
+    fn method_parsing(yaml: &str, expected: ThresholdMethod) {
+        assert_eq!(
+            serde_yaml::from_str::<ThresholdMethod>(yaml).unwrap(),
+            expected
+        );
+        assert_eq!(expected.to_string(), yaml);
+    }
+
+    #[test]
+    fn computed_threshold_serialisation() {
+        let computed_threshold = ComputedThreshold {
+            method: ThresholdMethod::Otsu,
+            threshold: Some(117.0),
+        };
+        assert_eq!(
+            computed_threshold.to_yaml().unwrap(),
+            "method: otsu\nthreshold: 117.0\n"
+        );
+    }
+
+    #[test]
+    fn method_parsing_when_unknown() {
+        assert!(serde_yaml::from_str::<ThresholdMethod>("adaptive").is_err());
+    }
+}